@@ -42,7 +42,9 @@ impl DerefMut for Endpoint {
 }
 
 pub trait ErasedIntoHandler {
-    fn call(&self, request: Incoming) -> PinnedBoxedResponse;
+    fn call(&self, request: Incoming) -> PinnedBoxedResponse<'_>;
+
+    fn streams_body(&self) -> bool;
 }
 
 #[derive(Debug)]
@@ -72,7 +74,11 @@ impl<H, T> ErasedIntoHandler for HandlerWrapper<H, T>
 where
     H: Handler<T> + Send + Sync,
 {
-    fn call(&self, request: Incoming) -> PinnedBoxedResponse {
+    fn call(&self, request: Incoming) -> PinnedBoxedResponse<'_> {
         Box::pin(self.handler.call(request))
     }
+
+    fn streams_body(&self) -> bool {
+        self.handler.streams_body()
+    }
 }
@@ -9,7 +9,13 @@ pub trait Handler<T> {
     fn call(
         &self,
         incoming: Incoming,
-    ) -> impl Future<Output = Result<Response, RhttpError>> + Send + Sync;
+    ) -> impl Future<Output = Result<Response<'_>, RhttpError>> + Send + Sync;
+
+    /// Whether the request body is handed over as it arrives instead of being
+    /// read whole and decoded before the handler runs.
+    fn streams_body(&self) -> bool {
+        false
+    }
 }
 
 impl<F, Fut, R> Handler<((),)> for F
@@ -21,7 +27,7 @@ where
     fn call(
         &self,
         _: Incoming,
-    ) -> impl Future<Output = Result<Response, RhttpError>> + Send + Sync {
+    ) -> impl Future<Output = Result<Response<'_>, RhttpError>> + Send + Sync {
         async move {
             let res = self().await;
             Ok(res.into_response())
@@ -39,7 +45,7 @@ where
     fn call(
         &self,
         incoming: Incoming,
-    ) -> impl Future<Output = Result<Response, RhttpError>> + Send + Sync {
+    ) -> impl Future<Output = Result<Response<'_>, RhttpError>> + Send + Sync {
         async move {
             let args = T1::extract(&incoming)?;
             let resp = self(args).await;
@@ -59,7 +65,7 @@ where
     fn call(
         &self,
         incoming: Incoming,
-    ) -> impl Future<Output = Result<Response, RhttpError>> + Send + Sync {
+    ) -> impl Future<Output = Result<Response<'_>, RhttpError>> + Send + Sync {
         async move {
             let args_1 = T1::extract(&incoming)?;
             let args_2 = T2::extract(&incoming)?;
@@ -1,39 +1,95 @@
+use bytes::Bytes;
+use futures::channel::mpsc;
 use http::{
     common::RhttpError,
     common::{
         RhttpError::{
-            ParsingHttpMethodErr, ParsingHttpProtocolErr, ParsingPathErr, ParsingRequestErr,
+            NotAcceptable, ParsingHttpMethodErr, ParsingHttpProtocolErr, ParsingPathErr,
+            ParsingRequestErr,
         },
         CRLF, FINAL_CRLF,
     },
+    cookie::CookieKeys,
+    forwarded::TrustedProxies,
+    headers::{Accept, HeaderType, HeaderValue},
     method::Method,
     request::Request,
+    url::{self, Url},
     version::ProtocolVersion,
 };
+use mime::Mime;
+use std::{
+    net::SocketAddr,
+    str,
+    sync::{Arc, Mutex},
+};
 
 #[derive(Debug, Default, Clone)]
 pub struct Incoming {
     pub request: Request,
-    pub query_params: String,
+    /// The request target, its path is also the path of `request`.
+    pub url: Url,
+    /// The path of the request target as the client sent it, before `url`
+    /// normalized it.
+    pub raw_path: String,
+    /// Percent-decoded, see [`crate::radix_tree::RouteMatch`].
     pub path_params: Vec<String>,
+    pub raw_path_params: Vec<String>,
     pub wildcard_param: String,
+    pub raw_wildcard_param: String,
+    /// Address of the connected peer, `None` when the request did not come over a socket.
+    pub peer_addr: Option<SocketAddr>,
+    /// Keys of the signed and private cookie jars, set by the router.
+    pub(crate) cookie_keys: Option<Arc<CookieKeys>>,
+    /// Proxies the `ClientIp` extractor believes, set by the router.
+    pub(crate) trusted_proxies: Option<Arc<TrustedProxies>>,
+    /// The body as it arrives for handlers streaming it, `request.body` is
+    /// empty then.
+    pub(crate) body_stream: Option<Arc<Mutex<Option<BodyReceiver>>>>,
 }
 
+/// Pieces of a request body sent by the router while the handler runs.
+pub(crate) type BodyReceiver = mpsc::Receiver<Result<Bytes, RhttpError>>;
+
 impl Incoming {
-    pub(crate) fn from(request_line: &str) -> Result<Self, RhttpError> {
+    pub(crate) fn from(request_bytes: &[u8]) -> Result<Self, RhttpError> {
         let mut incoming = Self::default();
-        let (first_line, other) = request_line
-            .split_once(CRLF)
-            .ok_or(ParsingRequestErr(String::new()))?;
-        let (request_headers, request_body) = other
-            .split_once(FINAL_CRLF)
+        let head_end = request_bytes
+            .windows(FINAL_CRLF.len())
+            .position(|window| window == FINAL_CRLF.as_bytes())
             .ok_or(ParsingRequestErr(String::new()))?;
-        incoming.parse_request_line(first_line)?;
-        incoming.parse_headers(request_headers);
-        request_body.clone_into(&mut incoming.request.body);
+        let request_head = &request_bytes[..head_end];
+        let mut lines = crlf_lines(request_head);
+        incoming.parse_request_line(str::from_utf8(lines.next().unwrap_or_default())?)?;
+        incoming.parse_headers(lines)?;
+        incoming.request.body =
+            Bytes::copy_from_slice(&request_bytes[head_end + FINAL_CRLF.len()..]);
         Ok(incoming)
     }
 
+    /// Picks the representation of `available` the client prefers, see
+    /// [`Accept::negotiate`]. Fails with `NotAcceptable`, answered with
+    /// `406 Not Acceptable`, when none of them is.
+    pub fn negotiate(&self, available: &[Mime]) -> Result<Mime, RhttpError> {
+        self.request
+            .headers
+            .typed_get::<Accept>()?
+            .unwrap_or_default()
+            .negotiate(available)
+            .cloned()
+            .ok_or(NotAcceptable)
+    }
+
+    /// The request body as the router receives it, once. `None` unless the
+    /// handler [streams the body](crate::handler::Handler::streams_body).
+    pub(crate) fn take_body_stream(&self) -> Option<BodyReceiver> {
+        self.body_stream
+            .as_ref()?
+            .lock()
+            .expect("body stream lock poisoned")
+            .take()
+    }
+
     pub(crate) fn get_request_method(&self) -> &Method {
         &self.request.request_line.method
     }
@@ -48,14 +104,10 @@ impl Incoming {
         let method = Method::parse_from_str(parts.next().ok_or(ParsingHttpMethodErr)?);
         self.request.add_method(method);
 
-        let path = parts.next().ok_or(ParsingPathErr)?;
-
-        if let Some((path, params)) = path.split_once('?') {
-            params.clone_into(&mut self.query_params);
-            self.request.add_path(path.to_owned());
-        } else {
-            self.request.add_path(path.to_owned());
-        }
+        let target = parts.next().ok_or(ParsingPathErr)?;
+        self.url = Url::parse_request_target(method, target)?;
+        self.raw_path = url::raw_path(target).to_owned();
+        self.request.add_path(self.url.path().to_owned());
 
         self.request
             .add_protocol_version(ProtocolVersion::parse_from_str(
@@ -65,14 +117,72 @@ impl Incoming {
         Ok(())
     }
 
-    fn parse_headers(&mut self, request_headers: &str) {
-        request_headers.split(CRLF).for_each(|headers| {
-            let (key, value) = headers
-                .split_once(": ")
-                .expect("properly formatted header item");
-            self.request
-                .add_header(key.to_owned(), value.to_owned())
-                .expect("");
-        });
+    /// Obsolete line folding and whitespace between the name and the colon
+    /// are rejected, RFC 9112 section 5. Values are kept as bytes, obs-text
+    /// such as Latin-1 is allowed.
+    fn parse_headers<'h>(
+        &mut self,
+        lines: impl Iterator<Item = &'h [u8]>,
+    ) -> Result<(), RhttpError> {
+        for line in lines.filter(|line| !line.is_empty()) {
+            if line.starts_with(b" ") || line.starts_with(b"\t") {
+                return Err(ParsingRequestErr(String::from("obsolete line folding")));
+            }
+            let colon = line.iter().position(|b| *b == b':').ok_or_else(|| {
+                ParsingRequestErr(format!(
+                    "malformed header line {:?}",
+                    line.escape_ascii().to_string()
+                ))
+            })?;
+            let key = &line[..colon];
+            if key.ends_with(b" ") || key.ends_with(b"\t") {
+                return Err(ParsingRequestErr(format!(
+                    "whitespace before colon in header {:?}",
+                    key.escape_ascii().to_string()
+                )));
+            }
+            self.request.headers.append(
+                HeaderType::from_bytes(key)?,
+                HeaderValue::from_bytes(line[colon + 1..].trim_ascii())?,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Lines separated by CRLF, a bare LF stays inside its line.
+fn crlf_lines(mut bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        if bytes.is_empty() {
+            return None;
+        }
+        let line_end = bytes
+            .windows(CRLF.len())
+            .position(|window| window == CRLF.as_bytes())
+            .unwrap_or(bytes.len());
+        let line = &bytes[..line_end];
+        bytes = bytes.get(line_end + CRLF.len()..).unwrap_or_default();
+        Some(line)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let incoming =
+            Incoming::from(b"GET / HTTP/1.1\r\nAccept: text/plain;q=0.5, text/html\r\n\r\n")
+                .unwrap();
+        let available = [mime::TEXT_PLAIN, mime::TEXT_HTML];
+        assert_eq!(incoming.negotiate(&available).unwrap(), mime::TEXT_HTML);
+        assert!(matches!(
+            incoming.negotiate(&[mime::APPLICATION_JSON]),
+            Err(NotAcceptable)
+        ));
+
+        let incoming = Incoming::from(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(incoming.negotiate(&available).unwrap(), mime::TEXT_PLAIN);
     }
 }
@@ -5,7 +5,7 @@
     clippy::all,
     clippy::dbg_macro,
     clippy::todo,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::enum_glob_use,
     clippy::mem_forget,
     clippy::unused_self,
@@ -15,7 +15,6 @@
     clippy::match_wildcard_for_single_variants,
     clippy::if_let_mutex,
     clippy::await_holding_lock,
-    clippy::match_on_vec_items,
     clippy::imprecise_flops,
     clippy::suboptimal_flops,
     clippy::lossy_float_literal,
@@ -44,11 +43,15 @@ pub mod endpoint;
 pub mod from_request;
 pub mod handler;
 pub mod incoming;
+pub mod proxy;
 pub mod radix_tree;
 pub mod router;
+pub mod test_client;
+pub mod upstream_pool;
 
 #[derive(Hash)]
 pub(crate) struct EndpointId {
+    host: Option<&'static str>,
     method: Method,
     path: &'static str,
 }
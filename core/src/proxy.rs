@@ -0,0 +1,709 @@
+use crate::{handler::Handler, incoming::Incoming, upstream_pool::UpstreamPool};
+#[cfg(unix)]
+use async_std::os::unix::net::UnixStream;
+use async_std::{future::timeout, io::BufReader, net::TcpStream};
+use futures::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use http::{
+    common::RhttpError::{self, ContentTooLarge, NoUpstreamAvailable, ParsingResponseErr, Timeout},
+    framing::{
+        read_response_head, request_framing, stream_body, write_pieces, BodyFraming, ParseOptions,
+        ResponseHead, Strictness,
+    },
+    headers::{ContentLength, HeaderMap, HeaderType, HeaderValue},
+    method::Method,
+    request::Request,
+    response::{IntoResponse, Response},
+    status_code::Status,
+    version::ProtocolVersion,
+};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{
+    fmt::{Display, Formatter},
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Headers describing a single connection, RFC 9110 section 7.6.1. They are
+/// never forwarded, neither are the headers listed in `Connection`.
+const HOP_BY_HOP_HEADERS: [&str; 9] = [
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Connection",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Upstream {
+    /// `host:port` of the upstream server.
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Upstream {
+    pub(crate) async fn connect(&self) -> Result<UpstreamStream, RhttpError> {
+        match self {
+            Upstream::Tcp(address) => {
+                let stream = TcpStream::connect(address.as_str()).await?;
+                stream.set_nodelay(true)?;
+                Ok(UpstreamStream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Upstream::Unix(path) => Ok(UpstreamStream::Unix(UnixStream::connect(path).await?)),
+        }
+    }
+
+    /// Value of the `Host` header sent upstream unless the original one is preserved.
+    pub(crate) fn host(&self) -> &str {
+        match self {
+            Upstream::Tcp(address) => address,
+            #[cfg(unix)]
+            Upstream::Unix(_) => "localhost",
+        }
+    }
+}
+
+impl Display for Upstream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Upstream::Tcp(address) => f.write_str(address),
+            #[cfg(unix)]
+            Upstream::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Target {
+    Upstream(Upstream),
+    Pool(Arc<UpstreamPool>),
+}
+
+/// Handler forwarding requests to an [`Upstream`] server or balancing them
+/// over an [`UpstreamPool`].
+///
+/// Request and response bodies are streamed in both directions as they
+/// arrive, request bodies keep their `Content-Encoding`. Upstreams that can't
+/// be reached or send an invalid response produce `502 Bad Gateway`,
+/// upstreams that don't answer in time `504 Gateway Timeout`. A pool without
+/// any available upstream produces `503 Service Unavailable`.
+///
+/// ```ignore
+/// app.register_path(
+///     Method::Get,
+///     "/legacy/*",
+///     Proxy::new(Upstream::Tcp(String::from("127.0.0.1:9000"))).rewrite_prefix("/legacy", "/"),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    target: Target,
+    rewrite: Option<(String, String)>,
+    preserve_host: bool,
+    connect_timeout: Duration,
+    timeout: Duration,
+}
+
+impl Proxy {
+    pub fn new(upstream: Upstream) -> Self {
+        Self::with_target(Target::Upstream(upstream))
+    }
+
+    pub fn balanced(pool: Arc<UpstreamPool>) -> Self {
+        Self::with_target(Target::Pool(pool))
+    }
+
+    fn with_target(target: Target) -> Self {
+        Self {
+            target,
+            rewrite: None,
+            preserve_host: false,
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Replaces the leading `from` segments of the path with `to`, paths not
+    /// starting with `from` are forwarded unchanged.
+    pub fn rewrite_prefix(mut self, from: &str, to: &str) -> Self {
+        self.rewrite = Some((from.to_owned(), to.to_owned()));
+        self
+    }
+
+    /// Sends the client's `Host` header upstream instead of the upstream address.
+    pub fn preserve_host(mut self, preserve_host: bool) -> Self {
+        self.preserve_host = preserve_host;
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// How long to wait for the upstream's response head once the request is sent.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn forward(&self, incoming: Incoming) -> Response<'static> {
+        let response = match &self.target {
+            Target::Upstream(upstream) => self.try_forward(upstream, &incoming).await,
+            Target::Pool(pool) => self.forward_balanced(pool, &incoming).await,
+        };
+        match response {
+            Ok(response) => response,
+            Err(Timeout) => Status::GatewayTimeout.into_response(),
+            Err(ContentTooLarge(_)) => Status::ContentTooLarge.into_response(),
+            Err(NoUpstreamAvailable(_)) => Status::ServiceUnavailable.into_response(),
+            Err(_) => Status::BadGateway.into_response(),
+        }
+    }
+
+    /// Upstreams that can't be connected to are counted as failed and the
+    /// next one is tried. Once the request is sent it is never retried, it
+    /// may not be idempotent.
+    async fn forward_balanced(
+        &self,
+        pool: &UpstreamPool,
+        incoming: &Incoming,
+    ) -> Result<Response<'static>, RhttpError> {
+        let mut tried = Vec::new();
+        loop {
+            let idx = pool
+                .select(incoming, &tried)
+                .ok_or_else(|| pool.unavailable())?;
+            let upstream = pool.upstream(idx);
+            let in_flight = pool.start(idx);
+            let Ok(stream) = with_timeout(self.connect_timeout, upstream.connect()).await else {
+                pool.record_failure(idx);
+                tried.push(idx);
+                continue;
+            };
+            return match self.exchange(stream, upstream, incoming).await {
+                Ok(mut response) => {
+                    pool.record_success(idx);
+                    response.body = in_flight.attach(response.body);
+                    Ok(response)
+                }
+                // the client sending too much is not the upstream's failure
+                Err(err @ ContentTooLarge(_)) => Err(err),
+                Err(err) => {
+                    pool.record_failure(idx);
+                    Err(err)
+                }
+            };
+        }
+    }
+
+    async fn try_forward(
+        &self,
+        upstream: &Upstream,
+        incoming: &Incoming,
+    ) -> Result<Response<'static>, RhttpError> {
+        let stream = with_timeout(self.connect_timeout, upstream.connect()).await?;
+        self.exchange(stream, upstream, incoming).await
+    }
+
+    async fn exchange(
+        &self,
+        stream: UpstreamStream,
+        upstream: &Upstream,
+        incoming: &Incoming,
+    ) -> Result<Response<'static>, RhttpError> {
+        let head_request = incoming.request.request_line.method == Method::Head;
+        let framing = request_framing(&incoming.request.headers)?;
+        let body = incoming.take_body_stream();
+        let mut request = self.upstream_request(upstream, incoming.clone())?;
+
+        let mut reader = BufReader::new(stream);
+        match body {
+            // sent on as it arrives, with the length or chunking it came with
+            Some(body) => {
+                match framing {
+                    BodyFraming::ContentLength(length) => {
+                        request.headers.typed_insert(ContentLength(length as u64))?;
+                    }
+                    BodyFraming::Chunked => {
+                        let chunked = HeaderValue::try_from("chunked")?;
+                        request
+                            .headers
+                            .insert(HeaderType::TransferEncoding, chunked);
+                    }
+                    BodyFraming::Empty | BodyFraming::CloseDelimited => {}
+                }
+                reader
+                    .get_mut()
+                    .write_all(&request.serialize_head()?)
+                    .await?;
+                write_pieces(reader.get_mut(), body, framing == BodyFraming::Chunked).await?;
+            }
+            None => {
+                reader.get_mut().write_all(&request.serialize()?).await?;
+                reader.get_mut().flush().await?;
+            }
+        }
+
+        let options = ParseOptions::new().head_request(head_request);
+        let ResponseHead {
+            status_line,
+            mut headers,
+            framing,
+        } = with_timeout(self.timeout, async {
+            loop {
+                let head = read_response_head(&mut reader, &options).await?;
+                // interim responses are not relayed, the client gets the final one
+                if !(100..200).contains(&head.status_line.status_code) {
+                    return Ok(head);
+                }
+                if head.status_line.status_code == 101 {
+                    return Err(ParsingResponseErr(String::from(
+                        "protocol upgrades are not proxied",
+                    )));
+                }
+            }
+        })
+        .await?;
+
+        strip_hop_by_hop(&mut headers);
+        Ok(Response {
+            status_line,
+            headers,
+            body: stream_body(reader, framing, Strictness::Strict),
+        })
+    }
+
+    fn upstream_request(
+        &self,
+        upstream: &Upstream,
+        incoming: Incoming,
+    ) -> Result<Request, RhttpError> {
+        let Incoming {
+            mut request,
+            url,
+            peer_addr,
+            ..
+        } = incoming;
+
+        let original_host = request
+            .headers
+            .get(&HeaderType::Host)
+            .and_then(|host| host.to_str().ok())
+            .map(str::to_owned);
+        strip_hop_by_hop(&mut request.headers);
+        // the body may have been re-framed, serializing sets the right length
+        request.headers.remove(&HeaderType::ContentLength);
+
+        let path = self.rewrite_path(&request.request_line.path);
+        match url.query() {
+            Some(query) => request.add_path(format!("{path}?{query}")),
+            None => request.add_path(path),
+        }
+        request.add_protocol_version(ProtocolVersion::Http11);
+
+        append_forwarded(&mut request.headers, peer_addr, original_host.as_deref())?;
+        if !self.preserve_host || original_host.is_none() {
+            request
+                .headers
+                .insert(HeaderType::Host, upstream.host().parse()?);
+        }
+        // one request per upstream connection keeps the framing of the streamed response simple
+        request
+            .headers
+            .insert(HeaderType::Connection, HeaderValue::try_from("close")?);
+        Ok(request)
+    }
+
+    fn rewrite_path(&self, path: &str) -> String {
+        let Some((from, to)) = &self.rewrite else {
+            return path.to_owned();
+        };
+        let from = from.trim_end_matches('/');
+        match path.strip_prefix(from) {
+            // only whole segments match, `/api` doesn't rewrite `/apis`
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                match format!("{}{rest}", to.trim_end_matches('/')) {
+                    rewritten if rewritten.is_empty() => String::from("/"),
+                    rewritten => rewritten,
+                }
+            }
+            _ => path.to_owned(),
+        }
+    }
+}
+
+impl Handler<()> for Proxy {
+    fn call(
+        &self,
+        incoming: Incoming,
+    ) -> impl Future<Output = Result<Response<'_>, RhttpError>> + Send + Sync {
+        async move { Ok(self.forward(incoming).await) }
+    }
+
+    fn streams_body(&self) -> bool {
+        true
+    }
+}
+
+pub(crate) async fn with_timeout<T, F>(duration: Duration, future: F) -> Result<T, RhttpError>
+where
+    F: Future<Output = Result<T, RhttpError>>,
+{
+    timeout(duration, future).await.map_err(|_| Timeout)?
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let hop_by_hop: Vec<HeaderType> = headers
+        .get_all(&HeaderType::Connection)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .chain(HOP_BY_HOP_HEADERS)
+        .filter_map(|name| name.parse::<HeaderType>().ok())
+        .collect();
+    headers.retain(|header_type, _| !hop_by_hop.contains(header_type));
+}
+
+/// Appends this hop to `X-Forwarded-For` and `Forwarded` (RFC 7239).
+fn append_forwarded(
+    headers: &mut HeaderMap,
+    peer_addr: Option<SocketAddr>,
+    host: Option<&str>,
+) -> Result<(), RhttpError> {
+    // a single field per name, proxies further down may not combine repeated ones
+    let mut append = |name: &str, element: String| -> Result<(), RhttpError> {
+        let header_type = HeaderType::Custom(name.to_owned());
+        let mut value = headers
+            .get_joined(&header_type)
+            .map(|existing| [existing.as_bytes(), b", "].concat())
+            .unwrap_or_default();
+        value.extend_from_slice(element.as_bytes());
+        headers.insert(header_type, HeaderValue::from_bytes(&value)?);
+        Ok(())
+    };
+
+    if let Some(peer_addr) = peer_addr {
+        append("X-Forwarded-For", peer_addr.ip().to_string())?;
+    }
+
+    let node = match peer_addr.map(|peer_addr| peer_addr.ip()) {
+        Some(IpAddr::V4(ip)) => ip.to_string(),
+        Some(IpAddr::V6(ip)) => format!("\"[{ip}]\""),
+        None => String::from("unknown"),
+    };
+    let mut element = format!("for={node}");
+    if let Some(host) = host {
+        element.push_str(&format!(";host={}", forwarded_value(host)));
+    }
+    element.push_str(";proto=http");
+    append("Forwarded", element)
+}
+
+/// Forwarded parameter values are tokens or quoted strings.
+fn forwarded_value(value: &str) -> String {
+    let is_token = !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+    if is_token {
+        value.to_owned()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum UpstreamStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl AsyncRead for UpstreamStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            UpstreamStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            UpstreamStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for UpstreamStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            UpstreamStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            UpstreamStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            UpstreamStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            UpstreamStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            UpstreamStream::Tcp(stream) => Pin::new(stream).poll_close(cx),
+            #[cfg(unix)]
+            UpstreamStream::Unix(stream) => Pin::new(stream).poll_close(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{router::Router, test_client::TestClient};
+    use async_std::{io::ReadExt, net::TcpListener, task};
+    use futures::AsyncBufReadExt;
+    use http::common::FINAL_CRLF;
+
+    /// Reads one request head from the stream and answers with `response`.
+    async fn answer<S>(stream: S, response: String) -> String
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        while !head.ends_with(FINAL_CRLF) {
+            if reader.read_line(&mut head).await.unwrap() == 0 {
+                break;
+            }
+        }
+        reader
+            .get_mut()
+            .write_all(response.as_bytes())
+            .await
+            .unwrap();
+        head
+    }
+
+    /// Upstream stand-in echoing the request head it received as the body.
+    async fn echo_upstream() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            while !head.ends_with(FINAL_CRLF) {
+                reader.read_line(&mut head).await.unwrap();
+            }
+            let mut body = vec![0; 5];
+            reader.read_exact(&mut body).await.unwrap();
+            head.push_str(std::str::from_utf8(&body).unwrap());
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nKeep-Alive: timeout=5\r\nX-Upstream: yes\r\n\r\n{head}",
+                head.len()
+            );
+            reader
+                .get_mut()
+                .write_all(response.as_bytes())
+                .await
+                .unwrap();
+        });
+        address
+    }
+
+    fn client(path: &'static str, proxy: Proxy) -> TestClient {
+        let mut router = Router::new();
+        router.register_path(Method::Post, path, proxy);
+        TestClient::new(router)
+    }
+
+    #[test]
+    fn test_rewrite_prefix() {
+        let upstream = Upstream::Tcp(String::from("127.0.0.1:9000"));
+        let proxy = Proxy::new(upstream.clone()).rewrite_prefix("/legacy", "/");
+        assert_eq!(proxy.rewrite_path("/legacy/a/b"), "/a/b");
+        assert_eq!(proxy.rewrite_path("/legacy"), "/");
+        assert_eq!(proxy.rewrite_path("/legacyx"), "/legacyx");
+        assert_eq!(proxy.rewrite_path("/other"), "/other");
+
+        let proxy = Proxy::new(upstream).rewrite_prefix("/api/", "/v2");
+        assert_eq!(proxy.rewrite_path("/api/users"), "/v2/users");
+        assert_eq!(proxy.rewrite_path("/api"), "/v2");
+    }
+
+    #[async_std::test]
+    async fn test_forwards_request() {
+        let upstream = echo_upstream().await;
+        let proxy = Proxy::new(Upstream::Tcp(upstream.clone())).rewrite_prefix("/legacy", "/");
+        let response = client("/legacy/*", proxy)
+            .post("/legacy/orders?page=2")
+            .header("Connection", "X-Hop")
+            .header("X-Hop", "dropped")
+            .header("X-Forwarded-For", "10.0.0.1")
+            .header("X-Kept", "kept")
+            .body("hello")
+            .peer_addr("192.0.2.7:4000".parse().unwrap())
+            .send()
+            .await;
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.header("x-upstream"), Some("yes"));
+        assert_eq!(response.header("keep-alive"), None);
+
+        let forwarded = response.text();
+        assert!(forwarded.starts_with("POST /orders?page=2 HTTP/1.1\r\n"));
+        assert!(forwarded.contains(&format!("Host: {upstream}\r\n")));
+        assert!(forwarded.contains("X-Kept: kept\r\n"));
+        assert!(!forwarded.contains("X-Hop"));
+        assert!(forwarded.contains("X-Forwarded-For: 10.0.0.1, 192.0.2.7\r\n"));
+        assert!(forwarded.contains("Forwarded: for=192.0.2.7;host=localhost;proto=http\r\n"));
+        assert!(forwarded.ends_with("\r\n\r\nhello"));
+    }
+
+    #[async_std::test]
+    async fn test_preserve_host() {
+        let upstream = echo_upstream().await;
+        let proxy = Proxy::new(Upstream::Tcp(upstream)).preserve_host(true);
+        let response = client("/", proxy).post("/").body("hello").send().await;
+        assert!(response.text().contains("Host: localhost\r\n"));
+        assert!(response
+            .text()
+            .contains("Forwarded: for=unknown;host=localhost;proto=http\r\n"));
+    }
+
+    #[async_std::test]
+    async fn test_streams_chunked_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = listener.local_addr().unwrap().to_string();
+        task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let response = "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+            answer(stream, response.to_owned()).await;
+        });
+
+        let mut router = Router::new();
+        router.register_path(Method::Get, "/", Proxy::new(Upstream::Tcp(upstream)));
+        let response = TestClient::new(router).get("/").send().await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.header("transfer-encoding"), Some("chunked"));
+        assert_eq!(response.text(), "hello world");
+    }
+
+    #[async_std::test]
+    async fn test_streams_chunked_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = listener.local_addr().unwrap().to_string();
+        let server = task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut received = String::new();
+            while !received.ends_with("\r\n0\r\n\r\n") {
+                reader.read_line(&mut received).await.unwrap();
+            }
+            let response = "HTTP/1.1 204 No Content\r\n\r\n";
+            reader
+                .get_mut()
+                .write_all(response.as_bytes())
+                .await
+                .unwrap();
+            received
+        });
+
+        let response = client("/", Proxy::new(Upstream::Tcp(upstream)))
+            .post("/")
+            .header("Transfer-Encoding", "chunked")
+            .body("hello")
+            .send()
+            .await;
+        assert_eq!(response.status(), 204);
+        let received = server.await;
+        assert!(received.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!received.contains("Content-Length"));
+        assert!(received.ends_with("\r\n\r\n5\r\nhello\r\n0\r\n\r\n"));
+    }
+
+    #[async_std::test]
+    async fn test_streamed_request_too_large() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = listener.local_addr().unwrap().to_string();
+        let mut router = Router::new();
+        router.register_path(Method::Post, "/", Proxy::new(Upstream::Tcp(upstream)));
+        router.max_body_length(3);
+        let client = TestClient::new(router);
+
+        let response = client.post("/").body("hello").send().await;
+        assert_eq!(response.status(), 413);
+        let response = client
+            .post("/")
+            .header("Transfer-Encoding", "chunked")
+            .body("hello")
+            .send()
+            .await;
+        assert_eq!(response.status(), 413);
+        drop(listener);
+    }
+
+    #[cfg(unix)]
+    #[async_std::test]
+    async fn test_unix_upstream() {
+        use async_std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("rhttp-proxy-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).await.unwrap();
+        let server = task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            answer(
+                stream,
+                String::from("HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok"),
+            )
+            .await
+        });
+
+        let mut router = Router::new();
+        router.register_path(Method::Get, "/", Proxy::new(Upstream::Unix(path.clone())));
+        let response = TestClient::new(router).get("/").send().await;
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.text(), "ok");
+        assert!(server.await.contains("Host: localhost\r\n"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_upstream_errors() {
+        // nothing listens on a port that was just released
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = closed.local_addr().unwrap().to_string();
+        drop(closed);
+        let mut router = Router::new();
+        router.register_path(Method::Get, "/", Proxy::new(Upstream::Tcp(upstream)));
+        assert_eq!(TestClient::new(router).get("/").send().await.status(), 502);
+
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = silent.local_addr().unwrap().to_string();
+        let proxy = Proxy::new(Upstream::Tcp(upstream)).timeout(Duration::from_millis(100));
+        let mut router = Router::new();
+        router.register_path(Method::Get, "/", proxy);
+        assert_eq!(TestClient::new(router).get("/").send().await.status(), 504);
+        drop(silent);
+    }
+}
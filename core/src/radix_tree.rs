@@ -1,4 +1,5 @@
-use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use http::url::percent_decode;
+use std::{cell::RefCell, collections::VecDeque, ops::Range, rc::Rc};
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 enum RadixNodeType {
@@ -33,6 +34,20 @@ impl PartialOrd for RadixNodeType {
 #[derive(Debug)]
 pub struct RadixTree(RadixNode);
 
+/// The endpoint a path matched and its parameters, percent-decoded. Paths
+/// are matched before decoding, so `a%2Fb` is the single parameter `a/b`,
+/// its raw form, as the client sent it, is kept for handlers that need to
+/// tell the two apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteMatch {
+    pub endpoint_id: u64,
+    pub path_params: Vec<String>,
+    pub raw_path_params: Vec<String>,
+    /// Decoded segment by segment and joined with `/`.
+    pub wildcard_param: String,
+    pub raw_wildcard_param: String,
+}
+
 impl Default for RadixTree {
     fn default() -> Self {
         Self(RadixNode {
@@ -59,19 +74,53 @@ impl RadixTree {
         self.0.insert(&mut path_segments, endpoint_id);
     }
 
-    pub fn find(&self, path: &str) -> Option<(u64, Vec<String>, String)> {
-        if let Some(match_result) = self.0.find(path) {
-            Some((
-                match_result.endpoint_id,
-                match_result.path_params,
-                match_result.wildcard_param,
-            ))
-        } else {
-            None
-        }
+    /// Matches the normalized `path`, the raw parameters are taken from the
+    /// same segments of `raw_path`. A parameter that does not decode to UTF-8
+    /// does not match.
+    pub fn find(&self, path: &str, raw_path: &str) -> Option<RouteMatch> {
+        let match_result = self.0.find(path)?;
+        let segments = path_segments(path);
+        let raw_segments = path_segments(raw_path);
+        // normalizing keeps every `/`, a segment is raw only when the match
+        // covers it whole
+        let raw = |range: Range<usize>, value: &str| match segments.get(range.clone()) {
+            Some(matched) if matched.join("/") == value && raw_segments.len() == segments.len() => {
+                raw_segments[range].join("/")
+            }
+            _ => value.to_owned(),
+        };
+        let path_params = match_result
+            .path_params
+            .iter()
+            .map(|(_, param)| percent_decode(param).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let wildcard_param = match_result
+            .wildcard_param
+            .split('/')
+            .map(|segment| percent_decode(segment).ok())
+            .collect::<Option<Vec<_>>>()?
+            .join("/");
+        Some(RouteMatch {
+            endpoint_id: match_result.endpoint_id,
+            path_params,
+            raw_path_params: match_result
+                .path_params
+                .iter()
+                .map(|(segment, param)| raw(*segment..*segment + 1, param))
+                .collect(),
+            wildcard_param,
+            raw_wildcard_param: raw(
+                match_result.wildcard_segment..segments.len(),
+                &match_result.wildcard_param,
+            ),
+        })
     }
 }
 
+fn path_segments(path: &str) -> Vec<&str> {
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
 struct RadixNode {
     constant: String,
@@ -138,7 +187,7 @@ impl RadixNode {
             // Exact - 3, PathParam - 2, WildCards - 3(not yet implemented).
             // Reason is when we match incoming path we iterate children from Exact matches through
             // PathParam and to WildCards makes find more simple
-            self.children.sort_by(|a, b| a.node_type.cmp(&b.node_type));
+            self.children.sort_by_key(|child| child.node_type);
             return;
         }
 
@@ -308,12 +357,15 @@ impl RadixNode {
         */
         let mut queue = VecDeque::new();
         let mut v = vec![];
+        let remaining_path = path_segments(path);
+        let segment_count = remaining_path.len();
 
         queue.push_back(SearchState {
             curr_node: self,
-            remaining_path: path.split('/').filter(|s| !s.is_empty()).collect(),
+            remaining_path,
             path_params: Rc::new(RefCell::new(&mut v)),
             wildcard_param: String::new(),
+            wildcard_segment: 0,
             priority: MatchPriority::Exact,
         });
 
@@ -324,18 +376,22 @@ impl RadixNode {
             remaining_path,
             path_params,
             wildcard_param,
+            wildcard_segment,
             priority,
         }) = queue.pop_front()
         {
-            if remaining_path.is_empty()
-                && curr_node.endpoint_id.is_some()
-                && (curr_match.is_none()
-                    || priority > curr_match.as_ref().expect("Search State").priority)
+            let better_match = curr_match
+                .as_ref()
+                .is_none_or(|curr_match| priority > curr_match.priority);
+            if let Some(endpoint_id) = curr_node
+                .endpoint_id
+                .filter(|_| remaining_path.is_empty() && better_match)
             {
                 curr_match = Some(MatchResult {
-                    endpoint_id: curr_node.endpoint_id.expect("CurrNode"),
+                    endpoint_id,
                     path_params: path_params.borrow().to_vec(),
                     wildcard_param,
+                    wildcard_segment,
                     priority,
                 });
             } else if !remaining_path.is_empty() {
@@ -367,20 +423,23 @@ impl RadixNode {
                                     remaining_path: remaining_path_segments,
                                     path_params: Rc::clone(&path_params),
                                     wildcard_param: wildcard_param.clone(),
+                                    wildcard_segment,
                                     priority,
                                 });
                             }
                         }
                         // second try match parameter
                         RadixNodeType::PathArgument => {
-                            (*path_params)
-                                .borrow_mut()
-                                .push(remaining_path[0].to_owned());
+                            (*path_params).borrow_mut().push((
+                                segment_count - remaining_path.len(),
+                                remaining_path[0].to_owned(),
+                            ));
                             queue.push_back(SearchState {
                                 curr_node: child,
                                 remaining_path: remaining_path[1..].to_vec(),
                                 path_params: Rc::clone(&path_params),
                                 wildcard_param: String::new(),
+                                wildcard_segment: 0,
                                 // idk if this is correct to do let me think about it
                                 priority: std::cmp::min(priority, MatchPriority::Parameter),
                             });
@@ -392,6 +451,7 @@ impl RadixNode {
                                 remaining_path: vec![],
                                 path_params: Rc::clone(&path_params),
                                 wildcard_param: remaining_path.join("/"),
+                                wildcard_segment: segment_count - remaining_path.len(),
                                 priority: MatchPriority::WildCard,
                             });
                         }
@@ -403,18 +463,21 @@ impl RadixNode {
     }
 }
 
+/// Parameters are kept with the index of the path segment they start at.
 struct SearchState<'a> {
     curr_node: &'a RadixNode,
     remaining_path: Vec<&'a str>,
-    path_params: Rc<RefCell<&'a mut Vec<String>>>,
+    path_params: Rc<RefCell<&'a mut Vec<(usize, String)>>>,
     wildcard_param: String,
+    wildcard_segment: usize,
     priority: MatchPriority,
 }
 
 struct MatchResult {
     endpoint_id: u64,
-    path_params: Vec<String>,
+    path_params: Vec<(usize, String)>,
     wildcard_param: String,
+    wildcard_segment: usize,
     priority: MatchPriority,
 }
 
@@ -441,12 +504,74 @@ mod tests {
         new_tree.insert("/use", 3_u64);
         new_tree.insert("/us", 4_u64);
         new_tree.insert("/dead/*end", 10_u64);
-        assert_eq!(0_u64, new_tree.find("user/9").expect("is there").0);
-        assert_eq!(1_u64, new_tree.find("/useless/9").expect("is there").0);
-        assert_eq!(2_u64, new_tree.find("/").expect("is there").0);
-        assert_eq!(2_u64, new_tree.find("/user").expect("is there").0);
-        assert_eq!(3_u64, new_tree.find("/use").expect("is there").0);
-        assert_eq!(4_u64, new_tree.find("/us").expect("is there").0);
-        assert_eq!(10_u64, new_tree.find("/dead/all/over").expect("is there").0);
+        assert_eq!(
+            0_u64,
+            new_tree
+                .find("user/9", "user/9")
+                .expect("is there")
+                .endpoint_id
+        );
+        assert_eq!(
+            1_u64,
+            new_tree
+                .find("/useless/9", "/useless/9")
+                .expect("is there")
+                .endpoint_id
+        );
+        assert_eq!(
+            2_u64,
+            new_tree.find("/", "/").expect("is there").endpoint_id
+        );
+        assert_eq!(
+            2_u64,
+            new_tree
+                .find("/user", "/user")
+                .expect("is there")
+                .endpoint_id
+        );
+        assert_eq!(
+            3_u64,
+            new_tree.find("/use", "/use").expect("is there").endpoint_id
+        );
+        assert_eq!(
+            4_u64,
+            new_tree.find("/us", "/us").expect("is there").endpoint_id
+        );
+        assert_eq!(
+            10_u64,
+            new_tree
+                .find("/dead/all/over", "/dead/all/over")
+                .expect("is there")
+                .endpoint_id
+        );
+    }
+
+    #[test]
+    fn test_decoded_params() {
+        let mut tree = RadixTree::new();
+        tree.insert("/order/:id", 0);
+        tree.insert("/files/*path", 1);
+
+        let route = tree.find("/order/caf%C3%A9", "/order/caf%C3%A9").unwrap();
+        assert_eq!(route.path_params, ["café"]);
+        assert_eq!(route.raw_path_params, ["caf%C3%A9"]);
+        let route = tree.find("/order/a%2Fb", "/order/a%2fb").unwrap();
+        assert_eq!(
+            (route.endpoint_id, route.path_params),
+            (0, vec![String::from("a/b")])
+        );
+        assert_eq!(route.raw_path_params, ["a%2fb"]);
+        let route = tree.find("/order/~user", "/order/%7euser").unwrap();
+        assert_eq!(route.path_params, ["~user"]);
+        assert_eq!(route.raw_path_params, ["%7euser"]);
+
+        let route = tree
+            .find("/files/a%20b/c%2Fd", "/files/a%20b/c%2fd")
+            .unwrap();
+        assert_eq!(route.wildcard_param, "a b/c/d");
+        assert_eq!(route.raw_wildcard_param, "a%20b/c%2fd");
+        let route = tree.find("/files/~a", "/files/%7Ea").unwrap();
+        assert_eq!(route.raw_wildcard_param, "%7Ea");
+        assert!(tree.find("/order/%FF", "/order/%FF").is_none());
     }
 }
@@ -7,31 +7,92 @@ use crate::{
 };
 
 use async_std::{
-    io::{BufReader, Read, Write},
+    io::{BufReader, Read},
     net::TcpListener,
 };
-use futures::AsyncBufReadExt;
-use futures::AsyncWriteExt;
-use futures::StreamExt;
+use bytes::Bytes;
+use futures::{channel::mpsc, SinkExt, Stream, StreamExt};
 use http::common::RhttpError;
 use http::common::RhttpError::{
-    HandlerNotFound, ListenerDefined, ListenerNotDefined, ParsingRequestErr,
+    ContentTooLarge, HandlerNotFound, InvalidFraming, InvalidHeader, InvalidHeaderName,
+    InvalidHeaderValue, InvalidUrl, ListenerDefined, ListenerNotDefined, MisdirectedRequest,
+    MissingHeader, NotAcceptable, ParsingRequestErr, Unauthorized, UnsupportedContentEncoding,
 };
 use http::{
+    compression::{Compression, Decompression},
+    conditional::Preconditions,
+    cookie::CookieKeys,
+    date::HttpDate,
+    forwarded::TrustedProxies,
+    framing::{body_pieces, read_body, read_head, request_framing, BodyFraming, Strictness},
+    headers::{
+        AcceptEncoding, Challenge, Date, Header, HeaderType, HeaderValue, Host, Quality,
+        WwwAuthenticate,
+    },
     method::Method,
+    range::RangeRequest,
     response::{IntoResponse, Response},
     status_code::Status,
+    version::ProtocolVersion,
 };
 use std::collections::HashMap;
 use std::hash::{DefaultHasher, Hash, Hasher};
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// Hostnames a virtual host answers, compared ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostPattern {
+    Exact(String),
+    /// `*.example.test`, kept as `.example.test`. Any subdomain matches, the
+    /// domain itself does not.
+    Wildcard(String),
+}
+
+impl HostPattern {
+    fn parse(host: &str) -> Self {
+        let host = normalize_hostname(host);
+        match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') => Self::Wildcard(suffix.to_owned()),
+            _ => Self::Exact(host),
+        }
+    }
+
+    fn matches(&self, hostname: &str) -> bool {
+        match self {
+            Self::Exact(host) => host == hostname,
+            Self::Wildcard(suffix) => hostname.len() > suffix.len() && hostname.ends_with(suffix),
+        }
+    }
+}
+
+/// A trailing dot names the same host, RFC 1034 section 3.1.
+fn normalize_hostname(hostname: &str) -> String {
+    hostname.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn method_trees() -> HashMap<Method, RadixTree> {
+    Method::iterator()
+        .map(|method| (*method, RadixTree::new()))
+        .collect()
+}
 
 #[derive(Debug)]
 pub struct Router {
     hasher: DefaultHasher,
     routes: HashMap<Method, RadixTree>,
+    /// Whether `routes` has any, they answer hosts without routes of their own.
+    default_host: bool,
+    hosts: Vec<(HostPattern, HashMap<Method, RadixTree>)>,
     handlers: HashMap<u64, Arc<Endpoint>>,
     listener: Option<TcpListener>,
+    cookie_keys: Option<Arc<CookieKeys>>,
+    compression: Option<Compression>,
+    decompression: Decompression,
+    max_body_length: u64,
+    challenges: Vec<Challenge>,
+    trusted_proxies: Option<Arc<TrustedProxies>>,
+    date: DateCache,
 }
 
 impl Default for Router {
@@ -42,20 +103,30 @@ impl Default for Router {
 
 impl Router {
     pub fn new() -> Self {
-        let mut routes = HashMap::new();
-        for method in Method::iterator() {
-            routes.insert(*method, RadixTree::new());
-        }
         Self {
             hasher: DefaultHasher::new(),
-            routes,
+            routes: method_trees(),
+            default_host: false,
+            hosts: Vec::new(),
             handlers: HashMap::new(),
             listener: None,
+            cookie_keys: None,
+            compression: None,
+            decompression: Decompression::default(),
+            max_body_length: 16 * 1024 * 1024,
+            challenges: Vec::new(),
+            trusted_proxies: None,
+            date: DateCache::default(),
         }
     }
 
-    fn hash_endpoint(&mut self, method: Method, path: &'static str) -> u64 {
-        EndpointId { method, path }.hash(&mut self.hasher);
+    fn hash_endpoint(
+        &mut self,
+        host: Option<&'static str>,
+        method: Method,
+        path: &'static str,
+    ) -> u64 {
+        EndpointId { host, method, path }.hash(&mut self.hasher);
         self.hasher.finish()
     }
 
@@ -65,14 +136,106 @@ impl Router {
         T: Send + Sync + 'static,
     {
         let new_endpoint = Arc::new(Endpoint::new(handler));
-        let endpoint_id = self.hash_endpoint(method, path);
+        let endpoint_id = self.hash_endpoint(None, method, path);
         self.handlers.insert(endpoint_id, new_endpoint);
+        self.default_host = true;
         self.routes
             .get_mut(&method)
             .expect("Method are already pre-populated!")
             .insert(path, endpoint_id);
     }
 
+    /// Registers `handler` for requests to `host` only, an exact hostname
+    /// such as `api.example.test` or `*.example.test` for its subdomains.
+    /// Exact names take precedence over wildcards, the longest wildcard over
+    /// shorter ones.
+    ///
+    /// Paths registered with [`Router::register_path`] answer every other
+    /// host, without any a request for an unknown host is answered with
+    /// `421 Misdirected Request`.
+    pub fn register_host_path<H, T>(
+        &mut self,
+        host: &'static str,
+        method: Method,
+        path: &'static str,
+        handler: H,
+    ) where
+        H: Handler<T> + Send + Sync + 'static,
+        T: Send + Sync + 'static,
+    {
+        let new_endpoint = Arc::new(Endpoint::new(handler));
+        let endpoint_id = self.hash_endpoint(Some(host), method, path);
+        self.handlers.insert(endpoint_id, new_endpoint);
+        let pattern = HostPattern::parse(host);
+        let index = match self.hosts.iter().position(|(known, _)| *known == pattern) {
+            Some(index) => index,
+            None => {
+                self.hosts.push((pattern, method_trees()));
+                self.hosts.len() - 1
+            }
+        };
+        self.hosts[index]
+            .1
+            .get_mut(&method)
+            .expect("Method are already pre-populated!")
+            .insert(path, endpoint_id);
+    }
+
+    /// Keys the `SignedCookieJar` and `PrivateCookieJar` extractors sign and
+    /// encrypt with, they fail with `500 Internal Server Error` without them.
+    pub fn cookie_keys(&mut self, keys: CookieKeys) {
+        self.cookie_keys = Some(Arc::new(keys));
+    }
+
+    /// Compresses response bodies with the coding the `Accept-Encoding` of
+    /// the request prefers, see [`Compression::apply`].
+    pub fn compression(&mut self, compression: Compression) {
+        self.compression = Some(compression);
+    }
+
+    /// Replaces the limits request bodies are decoded with, see
+    /// [`Decompression::apply`].
+    pub fn decompression(&mut self, decompression: Decompression) {
+        self.decompression = decompression;
+    }
+
+    /// Largest request body accepted as sent, longer ones are answered with
+    /// `413 Content Too Large`. Defaults to 16 MiB.
+    pub fn max_body_length(&mut self, max_length: u64) {
+        self.max_body_length = max_length;
+    }
+
+    /// Peers whose `Forwarded`, `X-Forwarded-*` and `X-Real-IP` headers the
+    /// `ClientIp` extractor follows, without them it is the peer address.
+    pub fn trusted_proxies(&mut self, proxies: TrustedProxies) {
+        self.trusted_proxies = Some(Arc::new(proxies));
+    }
+
+    /// The challenge sent with `401 Unauthorized` when the `BasicAuth` or
+    /// `BearerToken` extractor of its scheme rejects a request, replacing
+    /// the default one with a realm of `restricted`.
+    pub fn auth_challenge(&mut self, challenge: Challenge) {
+        self.challenges
+            .retain(|known| !known.scheme().eq_ignore_ascii_case(challenge.scheme()));
+        self.challenges.push(challenge);
+    }
+
+    fn challenge(&self, scheme: &str) -> Challenge {
+        let configured = self
+            .challenges
+            .iter()
+            .find(|challenge| challenge.scheme().eq_ignore_ascii_case(scheme));
+        match configured {
+            Some(challenge) => challenge.clone(),
+            None if scheme.eq_ignore_ascii_case("Basic") => {
+                Challenge::basic("restricted").expect("the realm is quotable")
+            }
+            None => Challenge::new(scheme)
+                .and_then(|challenge| challenge.with_param("realm", "restricted"))
+                .expect("extractors name their scheme with a token"),
+        }
+    }
+
     pub async fn bind_address(&mut self, address: &str) -> Result<(), RhttpError> {
         if self.listener.is_none() {
             self.listener = Some(TcpListener::bind(address).await?);
@@ -82,14 +245,123 @@ impl Router {
         }
     }
 
-    async fn handle_request<S: Read + Write + Unpin>(
+    async fn handle_request<S: Read + Unpin>(
         &self,
         stream: &mut S,
-    ) -> Result<Response, RhttpError> {
-        let request_parts = Self::load_request(stream).await?;
-        let mut request = Incoming::from(&request_parts)?;
+        peer_addr: Option<SocketAddr>,
+    ) -> Result<Response<'_>, RhttpError> {
+        let mut reader = BufReader::new(stream);
+        let head = read_head(&mut reader).await?;
+        let mut request = Incoming::from(&head)?;
+        let framing = request_framing(&request.request.headers)?;
+        if matches!(framing, BodyFraming::ContentLength(length) if length as u64 > self.max_body_length)
+        {
+            return Err(ContentTooLarge(self.max_body_length));
+        }
+        request.peer_addr = peer_addr;
+        request.cookie_keys.clone_from(&self.cookie_keys);
+        request.trusted_proxies.clone_from(&self.trusted_proxies);
         let handler = self.get_handler(&mut request)?;
-        handler.call(request).await
+        let method = *request.get_request_method();
+        // other methods change state, their handlers evaluate the conditions
+        let preconditions = matches!(method, Method::Get | Method::Head)
+            .then(|| Preconditions::from_request(method, &request.request.headers));
+        let range = (method == Method::Get)
+            .then(|| RangeRequest::from_request(method, &request.request.headers));
+        let accept_encoding = match &self.compression {
+            Some(_) => request
+                .request
+                .headers
+                .typed_get::<AcceptEncoding>()
+                .ok()
+                .flatten(),
+            None => None,
+        };
+        let mut response = if handler.streams_body() {
+            // the body is passed on as it arrives, in its content coding
+            let (sender, receiver) = mpsc::channel(1);
+            request.body_stream = Some(Arc::new(Mutex::new(Some(receiver))));
+            let pieces = body_pieces(&mut reader, framing, Strictness::Strict);
+            let (response, ()) = futures::join!(
+                handler.call(request),
+                send_body(pieces, self.max_body_length, sender)
+            );
+            response?
+        } else {
+            let body = read_body(
+                &mut reader,
+                framing,
+                Strictness::Strict,
+                self.max_body_length,
+            )
+            .await?;
+            request.request.body = self
+                .decompression
+                .apply(&mut request.request.headers, body)
+                .await?;
+            handler.call(request).await?
+        };
+        if let Some(preconditions) = preconditions {
+            response = preconditions.apply(response);
+        }
+        if let Some(range) = range {
+            response = range.apply(response);
+        }
+        if let Some(compression) = &self.compression {
+            response = compression.apply(accept_encoding.as_ref(), response).await;
+        }
+        Ok(response)
+    }
+
+    /// Reads a request from `stream` and runs the matching handler, errors are
+    /// turned into responses the same way the server sends them.
+    pub(crate) async fn respond<S: Read + Unpin>(
+        &self,
+        stream: &mut S,
+        peer_addr: Option<SocketAddr>,
+    ) -> Response<'_> {
+        let mut response = match self.handle_request(stream, peer_addr).await {
+            // a header a handler built from unchecked input must not split the response
+            Ok(response) if response.headers.validate().is_err() => {
+                Status::InternalServerError.into_response()
+            }
+            Ok(response) => response,
+            Err(err) => self.error_response(err),
+        };
+        // a response passed on from another server keeps the date it got there
+        response
+            .headers
+            .insert_if_absent(HeaderType::Date, self.date.value());
+        response
+    }
+
+    fn error_response<'rs>(&self, err: RhttpError) -> Response<'rs> {
+        match err {
+            HandlerNotFound(_)
+            | ParsingRequestErr(_)
+            | InvalidFraming(_)
+            | MissingHeader(_)
+            | InvalidHeader(_)
+            | InvalidHeaderName(_)
+            | InvalidHeaderValue(_)
+            | InvalidUrl(_) => Status::BadRequest.into_response(),
+            NotAcceptable => Status::NotAcceptable.into_response(),
+            ContentTooLarge(_) => Status::ContentTooLarge.into_response(),
+            MisdirectedRequest(_) => Status::MisdirectedRequest.into_response(),
+            // RFC 9110 section 15.5.16, tell the client what it can use instead
+            UnsupportedContentEncoding(_) => Status::UnsupportedMediaType
+                .into_response()
+                .with_header(AcceptEncoding(
+                    Decompression::CODINGS
+                        .iter()
+                        .map(|coding| (coding.to_string(), Quality::MAX))
+                        .collect(),
+                )),
+            Unauthorized(scheme) => Status::Unauthorized
+                .into_response()
+                .with_header(WwwAuthenticate(vec![self.challenge(&scheme)])),
+            _ => Status::InternalServerError.into_response(),
+        }
     }
 
     pub async fn listen(&self) -> Result<(), RhttpError> {
@@ -99,58 +371,132 @@ impl Router {
             .incoming()
             .for_each_concurrent(None, |stream| async {
                 if let Ok(mut stream) = stream {
-                    let response = match self.handle_request(&mut stream).await {
-                        Ok(r) => r,
-                        Err(err) => match err {
-                            HandlerNotFound(_) => Status::BadRequest.into_response(),
-                            _ => Status::InternalServerError.into_response(),
-                        },
-                    };
-                    let ser = response.serialize();
-                    stream.write_all(&ser).await.expect("result of execution");
+                    let peer_addr = stream.peer_addr().ok();
+                    let response = self.respond(&mut stream, peer_addr).await;
+                    // the peer went away or a streamed body failed half way,
+                    // either way there is nobody left to tell
+                    let _ = response.write_to(&mut stream).await;
                 }
             })
             .await;
         Ok(())
     }
 
-    async fn load_request<S: Read + Write + Unpin>(stream: &mut S) -> Result<String, RhttpError> {
-        let mut buf_reader = BufReader::new(stream);
-        if let Ok(load_buffer) = buf_reader.fill_buf().await {
-            let load_buffer = load_buffer.to_vec();
-            buf_reader.consume_unpin(load_buffer.len());
-            Ok(String::from_utf8(load_buffer)?)
-        } else {
-            Err(ParsingRequestErr(String::new()))
+    /// The routes of the host the request is for. An HTTP/1.1 request needs
+    /// exactly one `Host`, RFC 9112 section 3.2.
+    fn host_routes(&self, incoming: &Incoming) -> Result<&HashMap<Method, RadixTree>, RhttpError> {
+        let host = incoming.request.headers.typed_get::<Host>()?;
+        let version = incoming.request.request_line.protocol_version;
+        if host.is_none() && version == ProtocolVersion::Http11 {
+            return Err(MissingHeader(Host::name().to_string()));
+        }
+        if self.hosts.is_empty() {
+            return Ok(&self.routes);
+        }
+        // the host of an absolute-form target replaces Host, RFC 9112 section 3.2.2
+        let hostname = match incoming.url.host() {
+            Some(host) => normalize_hostname(host),
+            None => host
+                .map(|host| normalize_hostname(host.hostname()))
+                .unwrap_or_default(),
+        };
+        let exact = self.hosts.iter().find(|(pattern, _)| {
+            matches!(pattern, HostPattern::Exact(_)) && pattern.matches(&hostname)
+        });
+        let wildcard = || {
+            self.hosts
+                .iter()
+                .filter(|(pattern, _)| pattern.matches(&hostname))
+                .max_by_key(|(pattern, _)| match pattern {
+                    HostPattern::Exact(_) => 0,
+                    HostPattern::Wildcard(suffix) => suffix.len(),
+                })
+        };
+        match exact.or_else(wildcard) {
+            Some((_, routes)) => Ok(routes),
+            None if self.default_host => Ok(&self.routes),
+            None => Err(MisdirectedRequest(hostname)),
         }
     }
 
     fn get_handler(&self, incoming: &mut Incoming) -> Result<&BoxedHandler, RhttpError> {
+        let routes = self.host_routes(incoming)?;
         let method = incoming.get_request_method();
         let path = incoming.get_request_path();
-        let tree = self.routes.get(method).expect("Map of Methods!");
+        let tree = routes.get(method).expect("Map of Methods!");
 
-        let (endpoint_id, path_params, wildcard_param) =
-            tree.find(path).ok_or(HandlerNotFound(format!(
+        let route = tree
+            .find(path, &incoming.raw_path)
+            .ok_or(HandlerNotFound(format!(
                 "No handler found for path {} and method {}",
                 path,
                 method.to_str()
             )))?;
-        incoming.path_params = path_params;
-        incoming.wildcard_param = wildcard_param;
+        incoming.path_params = route.path_params;
+        incoming.raw_path_params = route.raw_path_params;
+        incoming.wildcard_param = route.wildcard_param;
+        incoming.raw_wildcard_param = route.raw_wildcard_param;
         Ok(self
             .handlers
-            .get(&endpoint_id)
+            .get(&route.endpoint_id)
             .expect("For provided endpoint id should be registered handler"))
     }
 }
 
+/// The `Date` of responses, formatted once a second rather than for each.
+#[derive(Debug, Default)]
+struct DateCache(Mutex<Option<(i64, HeaderValue)>>);
+
+impl DateCache {
+    fn value(&self) -> HeaderValue {
+        let now = HttpDate::now();
+        let mut cache = self.0.lock().expect("date cache lock poisoned");
+        match &*cache {
+            Some((second, value)) if *second == now.timestamp() => value.clone(),
+            _ => {
+                let value = Date(now).encode().expect("a date is a valid field value");
+                *cache = Some((now.timestamp(), value.clone()));
+                value
+            }
+        }
+    }
+}
+
+/// Sends the pieces of a request body to a handler streaming it until it is
+/// complete or the handler drops its end. A body growing beyond `max_length`
+/// ends with `ContentTooLarge`.
+async fn send_body<S>(
+    pieces: S,
+    max_length: u64,
+    mut sender: mpsc::Sender<Result<Bytes, RhttpError>>,
+) where
+    S: Stream<Item = Result<Bytes, RhttpError>>,
+{
+    let mut pieces = std::pin::pin!(pieces);
+    let mut length = 0u64;
+    while let Some(piece) = pieces.next().await {
+        let piece = piece.and_then(|piece| {
+            length += piece.len() as u64;
+            match length > max_length {
+                true => Err(ContentTooLarge(max_length)),
+                false => Ok(piece),
+            }
+        });
+        let last = piece.is_err();
+        if sender.send(piece).await.is_err() || last {
+            return;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // use std::collections::VecDeque;
     // use crate::from_request::PathParam;
     //
     use super::*;
+    use http::headers::{EntityTag, HeaderType, HeaderValue};
+    use http::url::Url;
     //
     fn setup_router() -> Router {
         Router::new()
@@ -160,9 +506,280 @@ mod tests {
     async fn test_double_bind_listener() {
         let mut router = setup_router();
         assert!(router.listener.is_none());
-        let _ = router.bind_address("127.0.0.1:8080").await;
+        let _ = router.bind_address("127.0.0.1:0").await;
         assert!(router.listener.is_some());
-        let _ = router.bind_address("127.0.0.1:8080").await;
+        let rebind = router.bind_address("127.0.0.1:0").await;
+        assert!(matches!(rebind, Err(ListenerDefined)));
+    }
+
+    async fn status_of(router: &Router, raw: &str) -> usize {
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        router
+            .respond(&mut stream, None)
+            .await
+            .status_line
+            .status_code
+    }
+
+    #[async_std::test]
+    async fn test_malformed_headers_are_rejected() {
+        let mut router = setup_router();
+        router.register_path(Method::Get, "/", || async { "ok" });
+
+        assert_eq!(
+            status_of(&router, "GET / HTTP/1.1\r\nHost: a\r\n\r\n").await,
+            200
+        );
+        assert_eq!(status_of(&router, "GET / HTTP/1.0\r\n\r\n").await, 200);
+        assert_eq!(
+            status_of(&router, "GET / HTTP/1.1\r\nHost:a\r\n\r\n").await,
+            200
+        );
+        for raw in [
+            "GET / HTTP/1.1\r\nHost : a\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nX-Long: a\r\n b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost a\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nBad\"Name: a\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nX-Bare: a\nInjected: b\r\n\r\n",
+        ] {
+            assert_eq!(status_of(&router, raw).await, 400, "{raw:?}");
+        }
+    }
+
+    #[async_std::test]
+    async fn test_responses_carry_the_date() {
+        let mut router = setup_router();
+        router.register_path(Method::Get, "/", || async { "ok" });
+        router.register_path(Method::Get, "/relayed", || async {
+            "ok".into_response()
+                .with_header(Date(HttpDate::from_timestamp(784_111_777)))
+        });
+        let date_of = |raw: &'static str| {
+            let router = &router;
+            async move {
+                let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+                let response = router.respond(&mut stream, None).await;
+                response.headers.typed_get::<Date>().unwrap().unwrap().0
+            }
+        };
+
+        let before = HttpDate::now();
+        let date = date_of("GET / HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert!(before <= date && date <= HttpDate::now());
+        let date = date_of("GET /missing HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert!(before <= date && date <= HttpDate::now());
+        assert_eq!(
+            date_of("GET /relayed HTTP/1.1\r\nHost: a\r\n\r\n").await,
+            HttpDate::from_timestamp(784_111_777)
+        );
+
+        let cache = DateCache::default();
+        let value = cache.value();
+        let cached = cache.value();
+        assert!(value == cached || cache.value() == cached);
+    }
+
+    #[async_std::test]
+    async fn test_injected_response_header_is_not_sent() {
+        let mut router = setup_router();
+        router.register_path(Method::Get, "/", || async {
+            let mut response = "ok".into_response();
+            response.headers.append(
+                HeaderType::Custom(String::from("X-Echo\r\nSet-Cookie")),
+                "a=1".parse::<HeaderValue>().unwrap(),
+            );
+            response
+        });
+        assert_eq!(
+            status_of(&router, "GET / HTTP/1.1\r\nHost: a\r\n\r\n").await,
+            500
+        );
+    }
+
+    #[async_std::test]
+    async fn test_virtual_hosts() {
+        let mut router = setup_router();
+        router.register_host_path("example.test", Method::Get, "/", || async { "apex" });
+        router.register_host_path("*.example.test", Method::Get, "/", || async { "sub" });
+        router.register_host_path("*.api.example.test", Method::Get, "/", || async { "api" });
+        router.register_host_path("API.example.test", Method::Get, "/", || async { "exact" });
+        let body = |raw: &'static str| {
+            let router = &router;
+            async move {
+                let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+                let response = router.respond(&mut stream, None).await;
+                String::from_utf8(response.body.as_bytes().unwrap().to_vec()).unwrap()
+            }
+        };
+
+        assert_eq!(
+            body("GET / HTTP/1.1\r\nHost: example.test\r\n\r\n").await,
+            "apex"
+        );
+        assert_eq!(
+            body("GET / HTTP/1.1\r\nHost: www.example.test:8080\r\n\r\n").await,
+            "sub"
+        );
+        assert_eq!(
+            body("GET / HTTP/1.1\r\nHost: v1.API.example.test.\r\n\r\n").await,
+            "api"
+        );
+        assert_eq!(
+            body("GET / HTTP/1.1\r\nHost: api.example.test\r\n\r\n").await,
+            "exact"
+        );
+        let raw = "GET / HTTP/1.1\r\nHost: other.test\r\n\r\n";
+        assert_eq!(status_of(&router, raw).await, 421);
+
+        router.register_path(Method::Get, "/", || async { "default" });
+        assert_eq!(status_of(&router, raw).await, 200);
+        let raw = "GET / HTTP/1.0\r\n\r\n";
+        assert_eq!(status_of(&router, raw).await, 200);
+        for raw in [
+            "GET / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: example.test\r\nHost: other.test\r\n\r\n",
+        ] {
+            assert_eq!(status_of(&router, raw).await, 400, "{raw:?}");
+        }
+    }
+
+    #[async_std::test]
+    async fn test_request_targets() {
+        let mut router = setup_router();
+        router.register_path(Method::Get, "/~user", |url: Url| async move {
+            format!("{:?}", url.query_pairs().unwrap())
+        });
+        router.register_host_path("proxied.test", Method::Get, "/~user", || async { "host" });
+
+        let raw = "GET /%7euser?name=J%C3%BCrgen+K HTTP/1.1\r\nHost: a\r\n\r\n";
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.body, r#"[("name", "Jürgen K")]"#);
+
+        let raw = "GET http://Proxied.test/~user HTTP/1.1\r\nHost: a\r\n\r\n";
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.body, "host");
+
+        for raw in [
+            "GET /~user#top HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /~user%2 HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET * HTTP/1.1\r\nHost: a\r\n\r\n",
+        ] {
+            assert_eq!(status_of(&router, raw).await, 400, "{raw:?}");
+        }
+    }
+
+    #[async_std::test]
+    async fn test_conditional_get() {
+        let mut router = setup_router();
+        router.register_path(Method::Get, "/", || async {
+            "ok".into_response().with_etag()
+        });
+        router.register_path(Method::Put, "/", || async { "stored" });
+        let etag = EntityTag::strong_from_body(b"ok");
+
+        let raw = format!("GET / HTTP/1.1\r\nHost: a\r\nIf-None-Match: {etag}\r\n\r\n");
+        let mut stream = futures::io::Cursor::new(raw.into_bytes());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.status_line.status_code, 304);
+        assert_eq!(response.body, "");
+        assert!(response.headers.contains_key(&HeaderType::ETag));
+
+        let raw = "GET / HTTP/1.1\r\nHost: a\r\nIf-Match: \"stale\"\r\n\r\n";
+        assert_eq!(status_of(&router, raw).await, 412);
+        let raw = "GET / HTTP/1.1\r\nHost: a\r\nIf-None-Match: \"stale\"\r\n\r\n";
+        assert_eq!(status_of(&router, raw).await, 200);
+        // handlers of unsafe methods evaluate the conditions themselves
+        let raw = "PUT / HTTP/1.1\r\nHost: a\r\nIf-Match: \"stale\"\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(status_of(&router, raw).await, 200);
+    }
+
+    #[async_std::test]
+    async fn test_range_request() {
+        let mut router = setup_router();
+        router.register_path(Method::Get, "/", || async { "0123456789" });
+        router.register_path(Method::Post, "/", || async { "0123456789" });
+
+        let raw = "GET / HTTP/1.1\r\nHost: a\r\nRange: bytes=-4\r\n\r\n";
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.status_line.status_code, 206);
+        assert_eq!(response.body, "6789");
+
+        let raw = "GET / HTTP/1.1\r\nHost: a\r\nRange: bytes=20-\r\n\r\n";
+        assert_eq!(status_of(&router, raw).await, 416);
+        // a range is only defined for GET
+        let raw = "POST / HTTP/1.1\r\nHost: a\r\nRange: bytes=-4\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(status_of(&router, raw).await, 200);
+    }
+
+    #[async_std::test]
+    async fn test_response_compression() {
+        let mut router = setup_router();
+        router.register_path(Method::Get, "/", || async { "text ".repeat(500) });
+        let raw = "GET / HTTP/1.1\r\nHost: a\r\nAccept-Encoding: gzip\r\n\r\n";
+
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert!(!response.headers.contains_key(&HeaderType::ContentEncoding));
+
+        router.compression(Compression::new());
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        let encoding = response.headers.get_joined(&HeaderType::ContentEncoding);
+        assert_eq!(encoding.unwrap(), "gzip");
+        assert!(response.body.as_bytes().unwrap().len() < 2500);
+    }
+
+    #[async_std::test]
+    async fn test_request_decompression() {
+        let mut router = setup_router();
+        router.register_path(Method::Post, "/", |body: String| async move { body });
+        router.decompression(Decompression::new().max_length(64));
+
+        let mut raw =
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Encoding: gzip\r\nContent-Length: ".to_vec();
+        // gzip of `{"id":7}`
+        let body = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xab, 0x56, 0xca, 0x4c,
+            0x51, 0xb2, 0x32, 0xaf, 0x05, 0x00, 0x43, 0x5f, 0x07, 0x12, 0x08, 0x00, 0x00, 0x00,
+        ];
+        raw.extend_from_slice(format!("{}\r\n\r\n", body.len()).as_bytes());
+        raw.extend_from_slice(&body);
+        let mut stream = futures::io::Cursor::new(raw);
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.status_line.status_code, 200);
+        assert_eq!(response.body, "{\"id\":7}");
+
+        let raw = "POST / HTTP/1.1\r\nHost: a\r\nContent-Encoding: compress\r\nContent-Length: 1\r\n\r\nx";
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.status_line.status_code, 415);
+        assert!(response.headers.contains_key(&HeaderType::AcceptEncoding));
+    }
+
+    #[async_std::test]
+    async fn test_request_body_too_large() {
+        let mut router = setup_router();
+        router.register_path(Method::Post, "/", |body: String| async move { body });
+        router.max_body_length(4);
+
+        let raw = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4\r\n\r\nabcd";
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.status_line.status_code, 200);
+
+        // refused from the header alone, nothing of the body has to arrive
+        let raw = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 18446744073709551615\r\n\r\n";
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.status_line.status_code, 413);
+
+        let raw = "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        let mut stream = futures::io::Cursor::new(raw.as_bytes().to_vec());
+        let response = router.respond(&mut stream, None).await;
+        assert_eq!(response.status_line.status_code, 413);
     }
 
     // #[test]
@@ -0,0 +1,229 @@
+use crate::router::Router;
+use bytes::Bytes;
+use futures::io::Cursor;
+use http::{
+    headers::{HeaderType, HeaderValue},
+    method::Method,
+    request::Request,
+    response::Response,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::net::SocketAddr;
+
+/// Drives a [`Router`] in memory, without binding a listener.
+///
+/// Requests go through the same serialization, parsing, routing and error
+/// handling as requests arriving over TCP, and the response is parsed back
+/// from the bytes the server would have written to the socket.
+#[derive(Debug)]
+pub struct TestClient {
+    router: Router,
+}
+
+impl TestClient {
+    pub fn new(router: Router) -> Self {
+        Self { router }
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> TestRequest<'_> {
+        let mut request = Request::new();
+        request.add_method(method);
+        request.add_path(path.to_owned());
+        request
+            .add_header(HeaderType::Host.to_string(), String::from("localhost"))
+            .expect("valid Host header");
+        TestRequest {
+            client: self,
+            request,
+            peer_addr: None,
+        }
+    }
+
+    pub fn get(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Get, path)
+    }
+
+    pub fn post(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Post, path)
+    }
+
+    pub fn put(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Put, path)
+    }
+
+    pub fn patch(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Patch, path)
+    }
+
+    pub fn delete(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Delete, path)
+    }
+}
+
+#[derive(Debug)]
+pub struct TestRequest<'c> {
+    client: &'c TestClient,
+    request: Request,
+    peer_addr: Option<SocketAddr>,
+}
+
+impl TestRequest<'_> {
+    /// Panics when the header is invalid, as a test helper should.
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.request
+            .add_header(key.to_owned(), value.to_owned())
+            .expect("valid header");
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.request.body = body.into();
+        self
+    }
+
+    pub fn json<T: Serialize>(self, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("value serializable to json");
+        self.header(
+            &HeaderType::ContentType.to_string(),
+            mime::APPLICATION_JSON.as_ref(),
+        )
+        .body(body)
+    }
+
+    /// Address the request pretends to come from, by default there is none.
+    pub fn peer_addr(mut self, peer_addr: SocketAddr) -> Self {
+        self.peer_addr = Some(peer_addr);
+        self
+    }
+
+    pub async fn send(self) -> TestResponse {
+        let request_bytes = self.request.serialize().expect("valid request");
+        let response = self
+            .client
+            .router
+            .respond(&mut Cursor::new(request_bytes), self.peer_addr)
+            .await;
+        let mut response_bytes = Vec::new();
+        response
+            .write_to(&mut response_bytes)
+            .await
+            .expect("response written");
+        let response = Response::parse(&response_bytes).expect("valid response");
+        TestResponse { response }
+    }
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    response: Response<'static>,
+}
+
+impl TestResponse {
+    pub fn status(&self) -> usize {
+        self.response.status_line.status_code
+    }
+
+    /// Value of `name`, `None` as well when it is not UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.response
+            .headers
+            .get(&HeaderType::Custom(name.to_owned()))
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Every value of a repeated header, in the order they were sent.
+    pub fn header_all(&self, name: &str) -> Vec<&str> {
+        self.response
+            .headers
+            .get_all(&HeaderType::Custom(name.to_owned()))
+            .filter_map(|value| value.to_str().ok())
+            .collect()
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&HeaderType, &HeaderValue)> {
+        self.response.headers.iter()
+    }
+
+    pub fn body(&self) -> &Bytes {
+        self.response
+            .body
+            .as_bytes()
+            .expect("parsed responses are held in memory")
+    }
+
+    /// Panics when the body is not valid UTF-8.
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(self.body()).expect("utf-8 body")
+    }
+
+    /// Panics when the body does not deserialize into `T`.
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(self.body()).expect("json body")
+    }
+
+    pub fn into_response(self) -> Response<'static> {
+        self.response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_request::{PathParam, QueryParams};
+    use serde::Deserialize;
+
+    async fn order(PathParam(order_id): PathParam<usize>) -> String {
+        format!("order_id: {order_id}")
+    }
+
+    #[derive(Deserialize)]
+    struct Paging {
+        page: usize,
+    }
+
+    async fn orders(QueryParams(paging): QueryParams<Paging>) -> String {
+        format!("page: {}", paging.page)
+    }
+
+    async fn echo(body: String) -> String {
+        body
+    }
+
+    fn client() -> TestClient {
+        let mut router = Router::new();
+        router.register_path(Method::Get, "/order/:order_id", order);
+        router.register_path(Method::Get, "/order", orders);
+        router.register_path(Method::Post, "/echo", echo);
+        TestClient::new(router)
+    }
+
+    #[async_std::test]
+    async fn test_get_with_params() {
+        let client = client();
+
+        let response = client.get("/order/7").send().await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text(), "order_id: 7");
+        assert_eq!(
+            response.header("content-type"),
+            Some("text/plain; charset=utf-8")
+        );
+
+        let response = client.get("/order?page=3").send().await;
+        assert_eq!(response.text(), "page: 3");
+    }
+
+    #[async_std::test]
+    async fn test_post_body() {
+        let response = client().post("/echo").json(&vec!["a", "b"]).send().await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.json::<Vec<String>>(), vec!["a", "b"]);
+    }
+
+    #[async_std::test]
+    async fn test_errors_map_to_status() {
+        let client = client();
+        assert_eq!(client.get("/missing").send().await.status(), 400);
+        assert_eq!(client.get("/order/not-a-number").send().await.status(), 500);
+    }
+}
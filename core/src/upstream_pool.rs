@@ -0,0 +1,562 @@
+use crate::{
+    incoming::Incoming,
+    proxy::{with_timeout, Upstream},
+};
+use async_std::{io::BufReader, task};
+use futures::{future::join_all, AsyncWriteExt, StreamExt};
+use http::{
+    body::Body,
+    common::RhttpError,
+    framing::{read_response_head, ParseOptions},
+    headers::HeaderType,
+    method::Method,
+    request::Request,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+/// Points each upstream occupies on the consistent hash ring, more points
+/// spread the keys more evenly.
+const VIRTUAL_NODES: usize = 64;
+
+/// How a pool picks the upstream for a request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Strategy {
+    #[default]
+    RoundRobin,
+    /// The upstream with the fewest requests in flight, response bodies
+    /// count until they are fully streamed.
+    LeastConnections,
+    /// Requests with the same key go to the same upstream as long as it is
+    /// available. Requests without the key are balanced round-robin.
+    ConsistentHash(HashKey),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashKey {
+    Header(String),
+    Cookie(String),
+}
+
+/// Periodic `GET` of `path` on every upstream, upstreams not answering with
+/// a 2xx or 3xx status in time are taken out until a later probe succeeds.
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    path: String,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl HealthCheck {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct UpstreamPoolBuilder {
+    name: String,
+    upstreams: Vec<Upstream>,
+    strategy: Strategy,
+    max_failures: usize,
+    ejection_time: Duration,
+    health_check: Option<HealthCheck>,
+}
+
+impl UpstreamPoolBuilder {
+    pub fn upstream(mut self, upstream: Upstream) -> Self {
+        self.upstreams.push(upstream);
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Consecutive failed requests after which an upstream is ejected.
+    pub fn max_failures(mut self, max_failures: usize) -> Self {
+        self.max_failures = max_failures.max(1);
+        self
+    }
+
+    /// How long an ejected upstream receives no requests.
+    pub fn ejection_time(mut self, ejection_time: Duration) -> Self {
+        self.ejection_time = ejection_time;
+        self
+    }
+
+    pub fn health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = Some(health_check);
+        self
+    }
+
+    /// Builds the pool and, when configured, starts probing it in the
+    /// background. Probing stops once the pool is dropped.
+    pub fn build(self) -> Arc<UpstreamPool> {
+        let members: Vec<Arc<Member>> = self
+            .upstreams
+            .into_iter()
+            .map(|upstream| Arc::new(Member::new(upstream)))
+            .collect();
+        let mut ring: Vec<(u64, usize)> = members
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, member)| {
+                (0..VIRTUAL_NODES)
+                    .map(move |node| (fnv1a(&format!("{}#{node}", member.upstream)), idx))
+            })
+            .collect();
+        ring.sort_unstable();
+
+        let pool = Arc::new(UpstreamPool {
+            name: self.name,
+            members,
+            strategy: self.strategy,
+            ring,
+            next: AtomicUsize::new(0),
+            max_failures: self.max_failures,
+            ejection_time: self.ejection_time,
+        });
+        if let Some(health_check) = self.health_check {
+            task::spawn(health_checks(Arc::downgrade(&pool), health_check));
+        }
+        pool
+    }
+}
+
+/// Named group of interchangeable upstreams a [`Proxy`](crate::proxy::Proxy)
+/// balances requests over.
+///
+/// ```ignore
+/// let pool = UpstreamPool::builder("api")
+///     .upstream(Upstream::Tcp(String::from("127.0.0.1:9000")))
+///     .upstream(Upstream::Tcp(String::from("127.0.0.1:9001")))
+///     .strategy(Strategy::LeastConnections)
+///     .health_check(HealthCheck::new("/health"))
+///     .build();
+/// app.register_path(Method::Get, "/api/*", Proxy::balanced(pool));
+/// ```
+#[derive(Debug)]
+pub struct UpstreamPool {
+    name: String,
+    members: Vec<Arc<Member>>,
+    strategy: Strategy,
+    /// Sorted `(hash, member index)` points for [`Strategy::ConsistentHash`].
+    ring: Vec<(u64, usize)>,
+    next: AtomicUsize,
+    max_failures: usize,
+    ejection_time: Duration,
+}
+
+impl UpstreamPool {
+    pub fn builder(name: &str) -> UpstreamPoolBuilder {
+        UpstreamPoolBuilder {
+            name: name.to_owned(),
+            upstreams: Vec::new(),
+            strategy: Strategy::default(),
+            max_failures: 3,
+            ejection_time: Duration::from_secs(30),
+            health_check: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Picks an available upstream for the request, skipping the members in `tried`.
+    pub(crate) fn select(&self, incoming: &Incoming, tried: &[usize]) -> Option<usize> {
+        let candidate = |idx: &usize| !tried.contains(idx) && self.members[*idx].is_available();
+        let count = self.members.len();
+        let round_robin = || {
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            (0..count).map(move |offset| (start + offset) % count)
+        };
+
+        match &self.strategy {
+            Strategy::RoundRobin => round_robin().find(candidate),
+            Strategy::LeastConnections => round_robin()
+                .filter(candidate)
+                .min_by_key(|idx| self.members[*idx].in_flight.load(Ordering::Relaxed)),
+            Strategy::ConsistentHash(hash_key) => match hash_key.extract(incoming) {
+                Some(key) => {
+                    let key = fnv1a(&key);
+                    let start = self.ring.partition_point(|(hash, _)| *hash < key);
+                    self.ring[start..]
+                        .iter()
+                        .chain(&self.ring[..start])
+                        .map(|(_, idx)| *idx)
+                        .find(candidate)
+                }
+                None => round_robin().find(candidate),
+            },
+        }
+    }
+
+    pub(crate) fn upstream(&self, idx: usize) -> &Upstream {
+        &self.members[idx].upstream
+    }
+
+    /// Counts a request as in flight until the returned guard is dropped.
+    pub(crate) fn start(&self, idx: usize) -> InFlight {
+        let member = Arc::clone(&self.members[idx]);
+        member.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(member)
+    }
+
+    pub(crate) fn record_success(&self, idx: usize) {
+        self.members[idx].failures.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_failure(&self, idx: usize) {
+        let member = &self.members[idx];
+        if member.failures.fetch_add(1, Ordering::Relaxed) + 1 >= self.max_failures {
+            member.failures.store(0, Ordering::Relaxed);
+            *member.ejected_until.lock().expect("ejection lock poisoned") =
+                Some(Instant::now() + self.ejection_time);
+        }
+    }
+
+    pub(crate) fn unavailable(&self) -> RhttpError {
+        RhttpError::NoUpstreamAvailable(self.name.clone())
+    }
+}
+
+#[derive(Debug)]
+struct Member {
+    upstream: Upstream,
+    in_flight: AtomicUsize,
+    /// Consecutive failed requests since the last success or ejection.
+    failures: AtomicUsize,
+    ejected_until: Mutex<Option<Instant>>,
+    /// Outcome of the last health probe.
+    healthy: AtomicBool,
+}
+
+impl Member {
+    fn new(upstream: Upstream) -> Self {
+        Self {
+            upstream,
+            in_flight: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            ejected_until: Mutex::new(None),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+            && self
+                .ejected_until
+                .lock()
+                .expect("ejection lock poisoned")
+                .is_none_or(|until| Instant::now() >= until)
+    }
+
+    async fn probe(&self, health_check: &HealthCheck) -> bool {
+        let status = with_timeout(health_check.timeout, async {
+            let mut request = Request::new();
+            request.add_method(Method::Get);
+            request.add_path(health_check.path.clone());
+            request.add_header(
+                HeaderType::Host.to_string(),
+                self.upstream.host().to_owned(),
+            )?;
+            request.add_header(HeaderType::Connection.to_string(), String::from("close"))?;
+
+            let mut reader = BufReader::new(self.upstream.connect().await?);
+            reader.get_mut().write_all(&request.serialize()?).await?;
+            reader.get_mut().flush().await?;
+            let head = read_response_head(&mut reader, &ParseOptions::default()).await?;
+            Ok(head.status_line.status_code)
+        })
+        .await;
+        status.is_ok_and(|status| (200..400).contains(&status))
+    }
+}
+
+/// Keeps a request counted against its upstream for [`Strategy::LeastConnections`].
+#[derive(Debug)]
+pub(crate) struct InFlight(Arc<Member>);
+
+impl InFlight {
+    /// Keeps counting until a streamed body has been sent or dropped.
+    pub(crate) fn attach(self, body: Body) -> Body {
+        match body {
+            Body::Stream(stream) => Body::from_stream(stream.map(move |piece| {
+                let _ = &self;
+                piece
+            })),
+            body @ Body::Full(_) => body,
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl HashKey {
+    fn extract(&self, incoming: &Incoming) -> Option<String> {
+        let header = |name: &str| {
+            incoming
+                .request
+                .headers
+                .get(&HeaderType::Custom(name.to_owned()))
+                .and_then(|value| value.to_str().ok())
+        };
+        match self {
+            HashKey::Header(name) => header(name).map(str::to_owned),
+            HashKey::Cookie(name) => header("Cookie")?
+                .split(';')
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(cookie, _)| cookie == name)
+                .map(|(_, value)| value.to_owned()),
+        }
+    }
+}
+
+async fn health_checks(pool: Weak<UpstreamPool>, health_check: HealthCheck) {
+    loop {
+        let Some(pool) = pool.upgrade() else {
+            return;
+        };
+        join_all(pool.members.iter().map(|member| async {
+            let healthy = member.probe(&health_check).await;
+            member.healthy.store(healthy, Ordering::Relaxed);
+        }))
+        .await;
+        drop(pool);
+        task::sleep(health_check.interval).await;
+    }
+}
+
+/// FNV-1a, stable across runs and platforms unlike the std hasher.
+fn fnv1a(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{proxy::Proxy, router::Router, test_client::TestClient};
+    use async_std::net::TcpListener;
+    use futures::AsyncBufReadExt;
+    use http::common::FINAL_CRLF;
+
+    /// Upstream stand-in answering every request with its `name`, `/health`
+    /// with `health_status`.
+    async fn stand_in(name: &'static str, health_status: usize) -> Upstream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        task::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                while !head.ends_with(FINAL_CRLF) {
+                    if reader.read_line(&mut head).await.unwrap_or(0) == 0 {
+                        break;
+                    }
+                }
+                let response = match head.starts_with("GET /health ") {
+                    true => format!("HTTP/1.1 {health_status} Probe\r\nContent-Length: 0\r\n\r\n"),
+                    false => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{name}",
+                        name.len()
+                    ),
+                };
+                let _ = reader.get_mut().write_all(response.as_bytes()).await;
+            }
+        });
+        Upstream::Tcp(address)
+    }
+
+    /// Address nothing listens on.
+    async fn closed_upstream() -> Upstream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Upstream::Tcp(listener.local_addr().unwrap().to_string())
+    }
+
+    fn client(pool: Arc<UpstreamPool>) -> TestClient {
+        let mut router = Router::new();
+        router.register_path(Method::Get, "/*", Proxy::balanced(pool));
+        TestClient::new(router)
+    }
+
+    async fn served_by(client: &TestClient, cookie: Option<&str>) -> String {
+        let mut request = client.get("/item");
+        if let Some(cookie) = cookie {
+            request = request.header("Cookie", cookie);
+        }
+        request.send().await.text().to_owned()
+    }
+
+    #[async_std::test]
+    async fn test_round_robin() {
+        let pool = UpstreamPool::builder("api")
+            .upstream(stand_in("a", 200).await)
+            .upstream(stand_in("b", 200).await)
+            .build();
+        assert_eq!(pool.name(), "api");
+        let client = client(pool);
+
+        let mut served = Vec::new();
+        for _ in 0..4 {
+            served.push(served_by(&client, None).await);
+        }
+        assert_eq!(served, ["a", "b", "a", "b"]);
+    }
+
+    #[async_std::test]
+    async fn test_least_connections() {
+        let pool = UpstreamPool::builder("api")
+            .upstream(stand_in("a", 200).await)
+            .upstream(stand_in("b", 200).await)
+            .strategy(Strategy::LeastConnections)
+            .build();
+        let busy = pool.start(0);
+        let client = client(Arc::clone(&pool));
+        assert_eq!(served_by(&client, None).await, "b");
+        assert_eq!(served_by(&client, None).await, "b");
+
+        drop(busy);
+        assert_eq!(pool.members[0].in_flight.load(Ordering::Relaxed), 0);
+        assert_eq!(pool.members[1].in_flight.load(Ordering::Relaxed), 0);
+    }
+
+    #[async_std::test]
+    async fn test_consistent_hash_by_cookie() {
+        let pool = UpstreamPool::builder("api")
+            .upstream(stand_in("a", 200).await)
+            .upstream(stand_in("b", 200).await)
+            .upstream(stand_in("c", 200).await)
+            .strategy(Strategy::ConsistentHash(HashKey::Cookie(String::from(
+                "session",
+            ))))
+            .build();
+        let client = client(Arc::clone(&pool));
+
+        for session in ["1", "2", "3", "4", "5"] {
+            let cookie = format!("theme=dark; session={session}");
+            let first = served_by(&client, Some(&cookie)).await;
+            for _ in 0..3 {
+                assert_eq!(served_by(&client, Some(&cookie)).await, first);
+            }
+        }
+
+        // a key only moves when its upstream becomes unavailable
+        let cookie = "session=1";
+        let first = served_by(&client, Some(cookie)).await;
+        let idx = ["a", "b", "c"]
+            .iter()
+            .position(|name| *name == first)
+            .unwrap();
+        pool.members[idx].healthy.store(false, Ordering::Relaxed);
+        assert_ne!(served_by(&client, Some(cookie)).await, first);
+    }
+
+    #[async_std::test]
+    async fn test_hash_by_header() {
+        let pool = UpstreamPool::builder("api")
+            .upstream(stand_in("a", 200).await)
+            .upstream(stand_in("b", 200).await)
+            .strategy(Strategy::ConsistentHash(HashKey::Header(String::from(
+                "X-Tenant",
+            ))))
+            .build();
+        let client = client(pool);
+
+        let first = client.get("/item").header("x-tenant", "acme").send().await;
+        assert_eq!(first.status(), 200);
+        for _ in 0..3 {
+            let response = client.get("/item").header("X-Tenant", "acme").send().await;
+            assert_eq!(response.text(), first.text());
+        }
+    }
+
+    #[async_std::test]
+    async fn test_passive_ejection() {
+        let pool = UpstreamPool::builder("api")
+            .upstream(closed_upstream().await)
+            .upstream(stand_in("b", 200).await)
+            .max_failures(2)
+            .build();
+        let balanced = client(Arc::clone(&pool));
+
+        // connection failures are retried on the next upstream
+        for _ in 0..4 {
+            assert_eq!(served_by(&balanced, None).await, "b");
+        }
+        assert!(!pool.members[0].is_available());
+
+        let pool = UpstreamPool::builder("empty")
+            .upstream(closed_upstream().await)
+            .max_failures(1)
+            .build();
+        let unavailable = client(pool);
+        assert_eq!(unavailable.get("/item").send().await.status(), 503);
+    }
+
+    #[async_std::test]
+    async fn test_ejection_expires() {
+        let pool = UpstreamPool::builder("api")
+            .upstream(stand_in("a", 200).await)
+            .max_failures(1)
+            .ejection_time(Duration::from_millis(50))
+            .build();
+        pool.record_failure(0);
+        assert!(!pool.members[0].is_available());
+        task::sleep(Duration::from_millis(60)).await;
+        assert!(pool.members[0].is_available());
+    }
+
+    #[async_std::test]
+    async fn test_active_health_checks() {
+        let pool = UpstreamPool::builder("api")
+            .upstream(stand_in("a", 503).await)
+            .upstream(stand_in("b", 204).await)
+            .upstream(closed_upstream().await)
+            .health_check(HealthCheck::new("/health").interval(Duration::from_millis(20)))
+            .build();
+
+        for _ in 0..100 {
+            if !pool.members[0].healthy.load(Ordering::Relaxed) {
+                break;
+            }
+            task::sleep(Duration::from_millis(10)).await;
+        }
+        task::sleep(Duration::from_millis(50)).await;
+        assert!(!pool.members[0].healthy.load(Ordering::Relaxed));
+        assert!(pool.members[1].healthy.load(Ordering::Relaxed));
+        assert!(!pool.members[2].healthy.load(Ordering::Relaxed));
+
+        let client = client(pool);
+        for _ in 0..3 {
+            assert_eq!(served_by(&client, None).await, "b");
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::common::RhttpError;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use std::{fmt::Formatter, pin::Pin};
+
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, RhttpError>> + Send>>;
+
+/// Message body, either held in memory or produced piece by piece.
+pub enum Body {
+    Full(Bytes),
+    /// Written to the wire as it is produced, chunk encoded unless the
+    /// message declares a `Content-Length`.
+    Stream(BodyStream),
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::empty()
+    }
+}
+
+impl Body {
+    pub fn empty() -> Self {
+        Body::Full(Bytes::new())
+    }
+
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, RhttpError>> + Send + 'static,
+    {
+        Body::Stream(Box::pin(stream))
+    }
+
+    /// The in-memory content, `None` for a streamed body.
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self {
+            Body::Full(bytes) => Some(bytes),
+            Body::Stream(_) => None,
+        }
+    }
+
+    pub fn is_stream(&self) -> bool {
+        matches!(self, Body::Stream(_))
+    }
+
+    /// Reads a streamed body to its end.
+    pub async fn collect(self) -> Result<Bytes, RhttpError> {
+        match self {
+            Body::Full(bytes) => Ok(bytes),
+            Body::Stream(mut stream) => {
+                let mut collected = BytesMut::new();
+                while let Some(chunk) = stream.next().await {
+                    collected.put(chunk?);
+                }
+                Ok(collected.freeze())
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Body {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Body::Full(bytes) => f.debug_tuple("Full").field(bytes).finish(),
+            Body::Stream(_) => f.debug_tuple("Stream").finish(),
+        }
+    }
+}
+
+/// Streams never compare equal, there is no way to look at them without consuming.
+impl PartialEq for Body {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Body::Full(this), Body::Full(other)) => this == other,
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq<str> for Body {
+    fn eq(&self, other: &str) -> bool {
+        self.as_bytes().is_some_and(|bytes| bytes == other)
+    }
+}
+
+impl PartialEq<&str> for Body {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(value: Bytes) -> Self {
+        Body::Full(value)
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Body::Full(Bytes::from(value))
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Body::Full(Bytes::from(value))
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(value: &'static str) -> Self {
+        Body::Full(Bytes::from_static(value.as_bytes()))
+    }
+}
@@ -0,0 +1,729 @@
+use crate::{
+    body::Body,
+    common::RhttpError::{self, ConnectionClosed, InvalidUrl, Timeout, TooManyRedirects},
+    framing::{read_body, read_response_head, BodyFraming, ParseOptions, ResponseHead, Strictness},
+    headers::{HeaderMap, HeaderType, Location},
+    method::Method,
+    request::Request,
+    response::Response,
+    version::ProtocolVersion,
+};
+use async_std::{future::timeout, io::BufReader, net::TcpStream};
+use bytes::Bytes;
+use futures::{AsyncBufReadExt, AsyncWriteExt};
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Asynchronous HTTP/1.1 client.
+///
+/// Connections are kept alive and pooled per `host:port`, so cloning the
+/// client is cheap and every clone shares the same pool.
+#[derive(Debug, Clone)]
+pub struct Client {
+    config: Arc<ClientConfig>,
+    pool: Arc<Mutex<HashMap<String, Vec<IdleConnection>>>>,
+}
+
+#[derive(Debug)]
+struct ClientConfig {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    max_redirects: usize,
+    max_idle_per_host: usize,
+    idle_timeout: Duration,
+    strictness: Strictness,
+    max_response_length: u64,
+}
+
+#[derive(Debug)]
+pub struct ClientBuilder {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    max_redirects: usize,
+    max_idle_per_host: usize,
+    idle_timeout: Duration,
+    strictness: Strictness,
+    max_response_length: u64,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder::new()
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            timeout: None,
+            connect_timeout: None,
+            max_redirects: 10,
+            max_idle_per_host: 8,
+            idle_timeout: Duration::from_secs(90),
+            strictness: Strictness::default(),
+            max_response_length: 16 * 1024 * 1024,
+        }
+    }
+
+    /// Upper bound for a single exchange, from writing the request until the
+    /// whole response body is read. Redirects get a fresh budget each.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Zero disables following redirects, the 3xx response is returned as is.
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn max_idle_per_host(mut self, max_idle_per_host: usize) -> Self {
+        self.max_idle_per_host = max_idle_per_host;
+        self
+    }
+
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Applies to serializing requests and parsing responses alike.
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Largest response body read, a longer one fails with `ContentTooLarge`.
+    /// Defaults to 16 MiB.
+    pub fn max_response_length(mut self, max_response_length: u64) -> Self {
+        self.max_response_length = max_response_length;
+        self
+    }
+
+    pub fn build(self) -> Client {
+        Client {
+            config: Arc::new(ClientConfig {
+                timeout: self.timeout,
+                connect_timeout: self.connect_timeout,
+                max_redirects: self.max_redirects,
+                max_idle_per_host: self.max_idle_per_host,
+                idle_timeout: self.idle_timeout,
+                strictness: self.strictness,
+                max_response_length: self.max_response_length,
+            }),
+            pool: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        ClientBuilder::new().build()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Sends `GET` to an absolute `http://` url.
+    pub async fn get(&self, url: &str) -> Result<Response<'static>, RhttpError> {
+        let (authority, path) = split_url(url)?;
+        let mut request = Request::new();
+        request.add_method(Method::Get);
+        request.add_path(path);
+        request.add_header(HeaderType::Host.to_string(), authority)?;
+        self.send(request).await
+    }
+
+    /// Sends the request to the server named by its `Host` header and
+    /// follows redirects up to the configured limit.
+    pub async fn send(&self, mut request: Request) -> Result<Response<'static>, RhttpError> {
+        let mut redirects = 0;
+        loop {
+            let authority = request
+                .headers
+                .get(&HeaderType::Host)
+                .ok_or(InvalidUrl(String::from("request has no Host header")))?
+                .to_str()?
+                .to_owned();
+            let response = self.execute(&authority, &request).await?;
+
+            let Some(location) = redirect_location(&response) else {
+                return Ok(response);
+            };
+            if self.config.max_redirects == 0 {
+                return Ok(response);
+            }
+            if redirects == self.config.max_redirects {
+                return Err(TooManyRedirects(redirects));
+            }
+            redirects += 1;
+            follow_redirect(
+                &mut request,
+                &authority,
+                response.status_line.status_code,
+                &location,
+            )?;
+        }
+    }
+
+    async fn execute(
+        &self,
+        authority: &str,
+        request: &Request,
+    ) -> Result<Response<'static>, RhttpError> {
+        with_timeout(self.config.timeout, self.exchange(authority, request)).await
+    }
+
+    async fn exchange(
+        &self,
+        authority: &str,
+        request: &Request,
+    ) -> Result<Response<'static>, RhttpError> {
+        let wire = request.serialize_with(self.config.strictness)?;
+        let options = ParseOptions::new()
+            .strictness(self.config.strictness)
+            .head_request(request.request_line.method == Method::Head);
+        let max_length = self.config.max_response_length;
+
+        if let Some(mut connection) = self.take_idle(authority) {
+            match connection.round_trip(&wire, &options, max_length).await {
+                Ok((response, keep_alive)) => {
+                    if keep_alive {
+                        self.put_idle(authority, connection);
+                    }
+                    return Ok(response);
+                }
+                // the server may have closed the pooled connection while it sat idle,
+                // it may also have received the request, so only a request that can
+                // be applied twice is tried once more on a fresh one
+                Err(err)
+                    if is_stale(&err)
+                        && !connection.answered
+                        && request.request_line.method.is_idempotent() => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut connection = self.connect(authority).await?;
+        let (response, keep_alive) = connection.round_trip(&wire, &options, max_length).await?;
+        if keep_alive {
+            self.put_idle(authority, connection);
+        }
+        Ok(response)
+    }
+
+    async fn connect(&self, authority: &str) -> Result<Connection, RhttpError> {
+        let address = socket_address(authority);
+        let stream = with_timeout(self.config.connect_timeout, async {
+            Ok(TcpStream::connect(address).await?)
+        })
+        .await?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            reader: BufReader::new(stream),
+            answered: false,
+        })
+    }
+
+    fn take_idle(&self, authority: &str) -> Option<Connection> {
+        let mut pool = self.pool.lock().expect("client pool lock poisoned");
+        let idle = pool.get_mut(authority)?;
+        while let Some(IdleConnection { connection, since }) = idle.pop() {
+            if since.elapsed() < self.config.idle_timeout {
+                return Some(connection);
+            }
+        }
+        None
+    }
+
+    fn put_idle(&self, authority: &str, connection: Connection) {
+        let mut pool = self.pool.lock().expect("client pool lock poisoned");
+        let idle = pool.entry(authority.to_owned()).or_default();
+        if idle.len() < self.config.max_idle_per_host {
+            idle.push(IdleConnection {
+                connection,
+                since: Instant::now(),
+            });
+        }
+    }
+}
+
+/// Whether `err` is how writing to or reading from a connection the server
+/// already closed fails.
+fn is_stale(err: &RhttpError) -> bool {
+    match err {
+        ConnectionClosed => true,
+        RhttpError::UnableToBindAddress(err) => matches!(
+            err.kind(),
+            io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+struct IdleConnection {
+    connection: Connection,
+    since: Instant,
+}
+
+#[derive(Debug)]
+struct Connection {
+    reader: BufReader<TcpStream>,
+    /// Whether any of the response to the last request arrived.
+    answered: bool,
+}
+
+impl Connection {
+    /// Writes the request and reads the final response, returns it together with
+    /// the information if the connection can be reused for another request.
+    /// A body longer than `max_length` fails with `ContentTooLarge`.
+    async fn round_trip(
+        &mut self,
+        wire: &[u8],
+        options: &ParseOptions,
+        max_length: u64,
+    ) -> Result<(Response<'static>, bool), RhttpError> {
+        self.answered = false;
+        self.reader.get_mut().write_all(wire).await?;
+        self.reader.get_mut().flush().await?;
+        self.answered = !self.reader.fill_buf().await?.is_empty();
+        loop {
+            let ResponseHead {
+                status_line,
+                headers,
+                framing,
+            } = read_response_head(&mut self.reader, options).await?;
+            let status_code = status_line.status_code;
+            // interim responses (100 Continue, 103 Early Hints...) precede the final one
+            if (100..200).contains(&status_code) && status_code != 101 {
+                continue;
+            }
+
+            let body = read_body(&mut self.reader, framing, options.strictness, max_length).await?;
+            let keep_alive = framing != BodyFraming::CloseDelimited
+                && is_keep_alive(status_line.version, &headers);
+            let response = Response {
+                status_line,
+                headers,
+                body: Body::Full(body),
+            };
+            return Ok((response, keep_alive));
+        }
+    }
+}
+
+async fn with_timeout<T, F>(duration: Option<Duration>, future: F) -> Result<T, RhttpError>
+where
+    F: Future<Output = Result<T, RhttpError>>,
+{
+    match duration {
+        Some(duration) => timeout(duration, future).await.map_err(|_| Timeout)?,
+        None => future.await,
+    }
+}
+
+fn is_keep_alive(version: ProtocolVersion, headers: &HeaderMap) -> bool {
+    let has_token = |token: &str| {
+        headers.get_all(&HeaderType::Connection).any(|connection| {
+            connection.to_str().is_ok_and(|connection| {
+                connection
+                    .split(',')
+                    .any(|option| option.trim().eq_ignore_ascii_case(token))
+            })
+        })
+    };
+    match version {
+        ProtocolVersion::Http11 => !has_token("close"),
+        ProtocolVersion::Http10 => has_token("keep-alive"),
+    }
+}
+
+fn redirect_location(response: &Response<'_>) -> Option<String> {
+    if !matches!(
+        response.status_line.status_code,
+        301 | 302 | 303 | 307 | 308
+    ) {
+        return None;
+    }
+    let location = response.headers.typed_get::<Location>().ok()??;
+    Some(location.as_str().to_owned())
+}
+
+/// Rewrites the request so it targets the redirect `location`.
+fn follow_redirect(
+    request: &mut Request,
+    current_authority: &str,
+    status_code: usize,
+    location: &str,
+) -> Result<(), RhttpError> {
+    let (authority, path) = if location.contains("://") {
+        split_url(location)?
+    } else if location.starts_with('/') {
+        (current_authority.to_owned(), location.to_owned())
+    } else {
+        // relative reference, resolved against the directory of the current path
+        let current = &request.request_line.path;
+        let current = current.split('?').next().unwrap_or_default();
+        let directory = &current[..=current.rfind('/').unwrap_or_default()];
+        (
+            current_authority.to_owned(),
+            format!("{directory}{location}"),
+        )
+    };
+
+    let method = request.request_line.method;
+    // 303 always switches to GET, 301 and 302 do it for POST as every browser does
+    if (status_code == 303 && method != Method::Head)
+        || (matches!(status_code, 301 | 302) && method == Method::Post)
+    {
+        request.add_method(Method::Get);
+        request.body = Bytes::new();
+        request.headers.remove(&HeaderType::ContentLength);
+        request.headers.remove(&HeaderType::ContentType);
+    }
+    if !socket_address(&authority).eq_ignore_ascii_case(&socket_address(current_authority)) {
+        // credentials belong to the server they were meant for
+        request.headers.remove(&HeaderType::Authorization);
+        request.headers.remove(&HeaderType::ProxyAuthorization);
+        request.headers.remove(&HeaderType::Cookie);
+    }
+    request.add_path(path);
+    request.headers.insert(HeaderType::Host, authority.parse()?);
+    Ok(())
+}
+
+/// Splits an absolute `http://` url into its authority and path with query.
+fn split_url(url: &str) -> Result<(String, String), RhttpError> {
+    let rest = url.strip_prefix("http://").ok_or(InvalidUrl(format!(
+        "only http:// urls are supported, got {url:?}"
+    )))?;
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        return Err(InvalidUrl(format!("missing host in {url:?}")));
+    }
+    let path = if path.starts_with('?') {
+        format!("/{path}")
+    } else {
+        path.to_owned()
+    };
+    Ok((authority.to_owned(), path))
+}
+
+/// Appends the default http port when the authority has none.
+fn socket_address(authority: &str) -> String {
+    let has_port = authority
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+    if has_port {
+        authority.to_owned()
+    } else {
+        format!("{authority}:80")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{RhttpError::ContentTooLarge, CRLF};
+    use async_std::{net::TcpListener, task};
+    use futures::AsyncBufReadExt;
+
+    async fn read_request_head(stream: &mut BufReader<TcpStream>) -> String {
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.expect("read line") == 0 || line == CRLF {
+                return head;
+            }
+            head.push_str(&line);
+        }
+    }
+
+    /// Spawns a server that answers every request on every accepted connection
+    /// with the next canned response, returns its address.
+    async fn serve(responses: Vec<&'static str>, connections: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let address = listener.local_addr().expect("local address").to_string();
+        task::spawn(async move {
+            let mut responses = responses.into_iter();
+            for _ in 0..connections {
+                let (stream, _) = listener.accept().await.expect("accept");
+                let mut stream = BufReader::new(stream);
+                while !read_request_head(&mut stream).await.is_empty() {
+                    let Some(response) = responses.next() else {
+                        return;
+                    };
+                    stream
+                        .get_mut()
+                        .write_all(response.as_bytes())
+                        .await
+                        .expect("write");
+                    if response.contains("Connection: close") {
+                        break;
+                    }
+                }
+            }
+        });
+        address
+    }
+
+    fn client() -> Client {
+        Client::builder().timeout(Duration::from_secs(5)).build()
+    }
+
+    #[async_std::test]
+    async fn test_keep_alive_reuses_connection() {
+        // the server accepts a single connection, the second request would hang otherwise
+        let address = serve(
+            vec![
+                "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfirst",
+                "HTTP/1.1 201 Created\r\nContent-Length: 6\r\n\r\nsecond",
+            ],
+            1,
+        )
+        .await;
+        let client = client();
+
+        let first = client
+            .get(&format!("http://{address}/"))
+            .await
+            .expect("first");
+        assert_eq!(first.status_line.status_code, 200);
+        assert_eq!(first.body, "first");
+
+        let second = client
+            .get(&format!("http://{address}/"))
+            .await
+            .expect("second");
+        assert_eq!(second.status_line.status_code, 201);
+        assert_eq!(second.status_line.reason_phrase, "Created");
+        assert_eq!(second.body, "second");
+    }
+
+    #[async_std::test]
+    async fn test_retries_only_idempotent_requests_on_stale_connections() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // answers the first request on every connection and drops the
+        // connection when the next one arrives, as after an idle timeout
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let address = listener.local_addr().expect("local address").to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        task::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.expect("accept");
+                counter.fetch_add(1, Ordering::SeqCst);
+                task::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    read_request_head(&mut stream).await;
+                    let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                    let _ = stream.get_mut().write_all(response.as_bytes()).await;
+                    read_request_head(&mut stream).await;
+                });
+            }
+        });
+        let client = client();
+        let url = format!("http://{address}/");
+
+        client.get(&url).await.expect("first");
+        let retried = client
+            .get(&url)
+            .await
+            .expect("retried on a fresh connection");
+        assert_eq!(retried.body, "ok");
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+        let mut post = Request::new();
+        post.add_method(Method::Post);
+        post.add_path(String::from("/"));
+        post.add_header(HeaderType::Host.to_string(), address)
+            .expect("valid header");
+        post.body = Bytes::from_static(b"once");
+        let result = client.send(post).await;
+        assert!(matches!(result, Err(ConnectionClosed)));
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+        let io_error = |kind| RhttpError::from(io::Error::from(kind));
+        assert!(is_stale(&io_error(io::ErrorKind::ConnectionReset)));
+        assert!(is_stale(&io_error(io::ErrorKind::BrokenPipe)));
+        assert!(!is_stale(&io_error(io::ErrorKind::PermissionDenied)));
+        assert!(!is_stale(&io_error(io::ErrorKind::InvalidData)));
+        assert!(!is_stale(&Timeout));
+    }
+
+    #[async_std::test]
+    async fn test_chunked_and_close_delimited_bodies() {
+        let address = serve(
+            vec![
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nTrailer: x\r\n\r\n",
+                "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nuntil the end",
+            ],
+            1,
+        )
+        .await;
+        let client = client();
+
+        let chunked = client
+            .get(&format!("http://{address}/"))
+            .await
+            .expect("chunked");
+        assert_eq!(chunked.body, "Wikipedia");
+
+        let close_delimited = client
+            .get(&format!("http://{address}/"))
+            .await
+            .expect("close");
+        assert_eq!(close_delimited.body, "until the end");
+    }
+
+    #[async_std::test]
+    async fn test_max_response_length() {
+        let address = serve(
+            vec![
+                "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nfits",
+                "HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\n",
+            ],
+            1,
+        )
+        .await;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .max_response_length(4)
+            .build();
+
+        let response = client.get(&format!("http://{address}/")).await;
+        assert_eq!(response.expect("fits").body, "fits");
+        let result = client.get(&format!("http://{address}/")).await;
+        assert!(matches!(result, Err(ContentTooLarge(4))));
+    }
+
+    #[async_std::test]
+    async fn test_follows_redirects() {
+        let address = serve(
+            vec![
+                "HTTP/1.1 302 Found\r\nLocation: /final\r\nContent-Length: 0\r\n\r\n",
+                "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndone",
+            ],
+            1,
+        )
+        .await;
+
+        let response = client()
+            .get(&format!("http://{address}/start"))
+            .await
+            .expect("redirect");
+        assert_eq!(response.status_line.status_code, 200);
+        assert_eq!(response.body, "done");
+    }
+
+    #[async_std::test]
+    async fn test_too_many_redirects() {
+        let address = serve(
+            vec![
+                "HTTP/1.1 307 Temporary Redirect\r\nLocation: /loop\r\nContent-Length: 0\r\n\r\n";
+                3
+            ],
+            1,
+        )
+        .await;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .max_redirects(2)
+            .build();
+
+        let result = client.get(&format!("http://{address}/loop")).await;
+        assert!(matches!(result, Err(TooManyRedirects(2))));
+    }
+
+    #[async_std::test]
+    async fn test_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let address = listener.local_addr().expect("local address");
+        let client = Client::builder()
+            .timeout(Duration::from_millis(100))
+            .build();
+
+        let result = client.get(&format!("http://{address}/")).await;
+        assert!(matches!(result, Err(Timeout)));
+        drop(listener);
+    }
+
+    #[test]
+    fn test_redirect_drops_credentials_for_other_hosts() {
+        let mut request = Request::new();
+        request.add_method(Method::Get);
+        request.add_path(String::from("/start"));
+        for (name, value) in [
+            ("Host", "example.test"),
+            ("Authorization", "Bearer secret"),
+            ("Proxy-Authorization", "Basic c2VjcmV0"),
+            ("Cookie", "session=1"),
+        ] {
+            request
+                .add_header(name.to_owned(), value.to_owned())
+                .expect("valid header");
+        }
+
+        follow_redirect(
+            &mut request,
+            "example.test",
+            302,
+            "http://EXAMPLE.test:80/a",
+        )
+        .expect("same host");
+        assert!(request.headers.contains_key(&HeaderType::Authorization));
+        assert!(request.headers.contains_key(&HeaderType::Cookie));
+
+        follow_redirect(&mut request, "example.test", 302, "http://other.test/b")
+            .expect("other host");
+        assert!(!request.headers.contains_key(&HeaderType::Authorization));
+        assert!(!request
+            .headers
+            .contains_key(&HeaderType::ProxyAuthorization));
+        assert!(!request.headers.contains_key(&HeaderType::Cookie));
+        assert_eq!(request.request_line.path, "/b");
+    }
+
+    #[test]
+    fn test_split_url() {
+        assert_eq!(
+            split_url("http://example.test:8080/a/b?c=d#frag").expect("valid"),
+            (String::from("example.test:8080"), String::from("/a/b?c=d"))
+        );
+        assert_eq!(
+            split_url("http://example.test").expect("valid"),
+            (String::from("example.test"), String::from("/"))
+        );
+        assert!(split_url("https://example.test").is_err());
+        assert_eq!(socket_address("example.test"), "example.test:80");
+        assert_eq!(socket_address("[::1]:8080"), "[::1]:8080");
+        assert_eq!(socket_address("[::1]"), "[::1]:80");
+    }
+}
@@ -37,5 +37,45 @@ pub enum RhttpError {
     #[error("error while parsing widlcard params! {}", self)]
     WildCardPathParamsErr,
     #[error("error while parsing request to string! {}", self)]
-    ParsingRequestToStringErr(#[from] std::string::FromUtf8Error)
+    ParsingRequestToStringErr(#[from] std::string::FromUtf8Error),
+    #[error("error while parsing response! {0}")]
+    ParsingResponseErr(String),
+    #[error("invalid message framing! {0}")]
+    InvalidFraming(String),
+    #[error("invalid url! {0}")]
+    InvalidUrl(String),
+    #[error("connection closed before a response was received")]
+    ConnectionClosed,
+    #[error("request timed out")]
+    Timeout,
+    #[error("too many redirects! stopped after {0}")]
+    TooManyRedirects(usize),
+    #[error("no upstream available in pool {0}")]
+    NoUpstreamAvailable(String),
+    #[error("missing header! {0}")]
+    MissingHeader(String),
+    #[error("invalid header! {0}")]
+    InvalidHeader(String),
+    #[error("invalid header name! {0:?}")]
+    InvalidHeaderName(String),
+    #[error("invalid header value! {0:?}")]
+    InvalidHeaderValue(String),
+    #[error("none of the available representations is acceptable")]
+    NotAcceptable,
+    #[error("invalid cookie! {0:?}")]
+    InvalidCookie(String),
+    #[error("cookie keys need a secret of at least 32 bytes")]
+    InvalidCookieKey,
+    #[error("no cookie keys registered on the router")]
+    MissingCookieKeys,
+    #[error("unsupported content encoding! {0:?}")]
+    UnsupportedContentEncoding(String),
+    #[error("content exceeds the limit of {0} bytes")]
+    ContentTooLarge(u64),
+    #[error("missing or invalid {0} credentials")]
+    Unauthorized(String),
+    #[error("the peer address of the connection is unknown")]
+    MissingPeerAddress,
+    #[error("no virtual host for {0:?}")]
+    MisdirectedRequest(String),
 }
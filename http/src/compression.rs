@@ -0,0 +1,506 @@
+use crate::{
+    body::Body,
+    common::RhttpError::{self, ContentTooLarge, ParsingRequestErr, UnsupportedContentEncoding},
+    framing::STREAM_CHUNK_LENGTH,
+    headers::{
+        AcceptEncoding, ContentCoding, ContentEncoding, ContentLength, ContentType, ETag,
+        EntityTag, HeaderMap, HeaderType, Vary,
+    },
+    response::{IntoResponse, Response},
+    status_code::Status,
+};
+use async_compression::{
+    futures::bufread::{
+        BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder,
+        ZstdDecoder, ZstdEncoder,
+    },
+    Level,
+};
+use bytes::Bytes;
+use futures::{io::Cursor, AsyncBufRead, AsyncRead, AsyncReadExt, TryStreamExt};
+use mime::Mime;
+use std::pin::Pin;
+
+type Coder = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Compresses response bodies with the content coding the client prefers,
+/// see [`Compression::apply`].
+#[derive(Debug, Clone)]
+pub struct Compression {
+    codings: Vec<ContentCoding>,
+    min_length: u64,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            codings: vec![
+                ContentCoding::Brotli,
+                ContentCoding::Zstd,
+                ContentCoding::Gzip,
+                ContentCoding::Deflate,
+            ],
+            min_length: 1024,
+        }
+    }
+}
+
+impl Compression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The codings to offer, the first wins when the client likes several
+    /// equally. `identity` in the list is ignored.
+    pub fn codings(mut self, codings: &[ContentCoding]) -> Self {
+        self.codings = codings
+            .iter()
+            .copied()
+            .filter(|coding| *coding != ContentCoding::Identity)
+            .collect();
+        self
+    }
+
+    /// In-memory bodies shorter than this are sent as they are unless the
+    /// client refuses `identity`. Streamed bodies are always compressed.
+    pub fn min_length(mut self, min_length: u64) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Encodes the body with the coding `accept` prefers, a response without
+    /// an `Accept-Encoding` is sent as it is. Bodies that are empty, already
+    /// encoded, partial or of an already compressed type are left alone.
+    ///
+    /// A strong `ETag` is weakened since it no longer matches the bytes sent,
+    /// and a client refusing every coding and `identity` gets a `406`.
+    pub async fn apply<'rs>(
+        &self,
+        accept: Option<&AcceptEncoding>,
+        mut response: Response<'rs>,
+    ) -> Response<'rs> {
+        if !self.is_compressible(&response) {
+            return response;
+        }
+        add_vary(&mut response);
+        let Some(accept) = accept else {
+            return response;
+        };
+        let mut available = self.codings.clone();
+        let small = response
+            .body
+            .as_bytes()
+            .is_some_and(|body| (body.len() as u64) < self.min_length);
+        match small {
+            true => available.insert(0, ContentCoding::Identity),
+            false => available.push(ContentCoding::Identity),
+        }
+        match accept.negotiate(&available) {
+            None => Status::NotAcceptable.into_response(),
+            Some(ContentCoding::Identity) => response,
+            Some(coding) => encode(response, coding).await,
+        }
+    }
+
+    fn is_compressible(&self, response: &Response<'_>) -> bool {
+        let status = response.status_line.status_code;
+        let empty = response.body.as_bytes().is_some_and(|body| body.is_empty());
+        let compressed = response
+            .headers
+            .typed_get::<ContentType>()
+            .ok()
+            .flatten()
+            .is_some_and(|content_type| is_compressed_type(&content_type.0));
+        !self.codings.is_empty()
+            && (200..300).contains(&status)
+            && status != Status::NoContent.status_code()
+            && status != Status::PartialContent.status_code()
+            && !empty
+            && !compressed
+            && !response.headers.contains_key(&HeaderType::ContentEncoding)
+            && !response.headers.contains_key(&HeaderType::ContentRange)
+    }
+}
+
+/// Decodes request bodies sent with a `Content-Encoding`, before any
+/// extractor sees them.
+#[derive(Debug, Clone, Copy)]
+pub struct Decompression {
+    max_length: u64,
+}
+
+impl Default for Decompression {
+    fn default() -> Self {
+        Self {
+            max_length: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl Decompression {
+    /// The codings request bodies can be sent with.
+    pub const CODINGS: [ContentCoding; 4] = [
+        ContentCoding::Gzip,
+        ContentCoding::Deflate,
+        ContentCoding::Brotli,
+        ContentCoding::Zstd,
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest body decoding may produce, a few kilobytes of compressed
+    /// data can expand to gigabytes.
+    pub fn max_length(mut self, max_length: u64) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Undoes the codings of `Content-Encoding` in reverse order and drops
+    /// the header, `Content-Length` is set to the decoded length.
+    ///
+    /// Fails with `UnsupportedContentEncoding` for a coding this crate does
+    /// not implement, `ContentTooLarge` when the decoded body exceeds the
+    /// limit and `ParsingRequestErr` for corrupt data.
+    pub async fn apply(&self, headers: &mut HeaderMap, body: Bytes) -> Result<Bytes, RhttpError> {
+        let codings = match headers.typed_get::<ContentEncoding>() {
+            Ok(Some(ContentEncoding(codings))) => codings,
+            Ok(None) => return Ok(body),
+            Err(_) => {
+                let encoding = headers.get_joined(&HeaderType::ContentEncoding);
+                let encoding = encoding.map(|value| value.as_bytes().escape_ascii().to_string());
+                return Err(UnsupportedContentEncoding(encoding.unwrap_or_default()));
+            }
+        };
+        let mut body = body;
+        for coding in codings.into_iter().rev() {
+            let mut decoded = Vec::new();
+            decoder(coding, Cursor::new(body))
+                .take(self.max_length + 1)
+                .read_to_end(&mut decoded)
+                .await
+                .map_err(|err| ParsingRequestErr(format!("invalid {coding} content: {err}")))?;
+            if decoded.len() as u64 > self.max_length {
+                return Err(ContentTooLarge(self.max_length));
+            }
+            body = Bytes::from(decoded);
+        }
+        headers.remove(&HeaderType::ContentEncoding);
+        headers.typed_insert(ContentLength(body.len() as u64))?;
+        Ok(body)
+    }
+}
+
+/// Formats that are compressed already, and event streams that have to
+/// reach the client event by event.
+fn is_compressed_type(mime: &Mime) -> bool {
+    match (mime.type_().as_str(), mime.subtype().as_str()) {
+        ("image", subtype) => subtype != "svg",
+        ("audio" | "video", _) => true,
+        ("font", subtype) => matches!(subtype, "woff" | "woff2"),
+        ("text", "event-stream") => true,
+        ("application", subtype) => matches!(
+            subtype,
+            "zip"
+                | "gzip"
+                | "x-gzip"
+                | "zstd"
+                | "x-bzip2"
+                | "x-xz"
+                | "x-7z-compressed"
+                | "vnd.rar"
+                | "x-rar-compressed"
+        ),
+        _ => false,
+    }
+}
+
+/// Adds `Accept-Encoding` to `Vary`, the response depends on it whether it
+/// ends up encoded or not.
+fn add_vary(response: &mut Response<'_>) {
+    let vary = match response.headers.typed_get::<Vary>().ok().flatten() {
+        Some(Vary::Any) => return,
+        Some(Vary::Headers(mut headers)) => {
+            if headers.contains(&HeaderType::AcceptEncoding) {
+                return;
+            }
+            headers.push(HeaderType::AcceptEncoding);
+            headers
+        }
+        None => vec![HeaderType::AcceptEncoding],
+    };
+    response
+        .headers
+        .typed_insert(Vary::Headers(vary))
+        .expect("decoded names are tokens");
+}
+
+fn encoder<R>(coding: ContentCoding, reader: R) -> Coder
+where
+    R: AsyncBufRead + Send + 'static,
+{
+    match coding {
+        ContentCoding::Gzip => Box::pin(GzipEncoder::new(reader)),
+        ContentCoding::Deflate => Box::pin(ZlibEncoder::new(reader)),
+        // the default quality of brotli is meant for static content
+        ContentCoding::Brotli => Box::pin(BrotliEncoder::with_quality(reader, Level::Precise(4))),
+        ContentCoding::Zstd => Box::pin(ZstdEncoder::new(reader)),
+        ContentCoding::Identity => Box::pin(reader),
+    }
+}
+
+fn decoder<R>(coding: ContentCoding, reader: R) -> Coder
+where
+    R: AsyncBufRead + Send + 'static,
+{
+    match coding {
+        ContentCoding::Gzip => Box::pin(GzipDecoder::new(reader)),
+        ContentCoding::Deflate => Box::pin(ZlibDecoder::new(reader)),
+        ContentCoding::Brotli => Box::pin(BrotliDecoder::new(reader)),
+        ContentCoding::Zstd => Box::pin(ZstdDecoder::new(reader)),
+        ContentCoding::Identity => Box::pin(reader),
+    }
+}
+
+async fn encode(mut response: Response<'_>, coding: ContentCoding) -> Response<'_> {
+    response.headers.remove(&HeaderType::ContentLength);
+    response = response.with_header(ContentEncoding(vec![coding]));
+    if let Ok(Some(ETag(etag))) = response.headers.typed_get::<ETag>() {
+        if !etag.is_weak() {
+            let weak = EntityTag::weak(etag.tag()).expect("the tag was valid already");
+            response = response.with_header(ETag(weak));
+        }
+    }
+    match std::mem::take(&mut response.body) {
+        Body::Full(body) => {
+            let mut encoded = Vec::new();
+            if encoder(coding, Cursor::new(body))
+                .read_to_end(&mut encoded)
+                .await
+                .is_err()
+            {
+                return Status::InternalServerError.into_response();
+            }
+            response = response.with_header(ContentLength(encoded.len() as u64));
+            response.body = Body::from(encoded);
+        }
+        Body::Stream(stream) => {
+            let reader = stream.map_err(std::io::Error::other).into_async_read();
+            response.body = read_encoded(encoder(coding, reader));
+        }
+    }
+    response
+}
+
+fn read_encoded(encoder: Coder) -> Body {
+    Body::from_stream(futures::stream::try_unfold(
+        encoder,
+        |mut encoder| async move {
+            let mut piece = vec![0; STREAM_CHUNK_LENGTH];
+            let read = encoder.read(&mut piece).await?;
+            if read == 0 {
+                return Ok(None);
+            }
+            piece.truncate(read);
+            Ok(Some((piece.into(), encoder)))
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::{Header, HeaderValue};
+
+    fn accept(value: &str) -> AcceptEncoding {
+        AcceptEncoding::decode([HeaderValue::try_from(value).unwrap()].iter()).unwrap()
+    }
+
+    fn header(response: &Response<'_>, name: HeaderType) -> Option<String> {
+        let value = response.headers.get_joined(&name)?;
+        Some(value.to_str().unwrap().to_owned())
+    }
+
+    fn text() -> String {
+        "compressible text ".repeat(100)
+    }
+
+    #[async_std::test]
+    async fn test_compress_full_body() {
+        let compression = Compression::new();
+        let response = text().into_response().with_etag();
+        let response = compression
+            .apply(Some(&accept("br;q=0.5, gzip, zstd;q=0.9")), response)
+            .await;
+        assert_eq!(
+            header(&response, HeaderType::ContentEncoding).unwrap(),
+            "gzip"
+        );
+        assert_eq!(
+            header(&response, HeaderType::Vary).unwrap(),
+            "Accept-Encoding"
+        );
+        assert!(header(&response, HeaderType::ETag)
+            .unwrap()
+            .starts_with("W/"));
+
+        let body = response.body.as_bytes().unwrap().clone();
+        assert_eq!(
+            header(&response, HeaderType::ContentLength).unwrap(),
+            body.len().to_string()
+        );
+        let mut decoded = String::new();
+        GzipDecoder::new(Cursor::new(body))
+            .read_to_string(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, text());
+    }
+
+    #[async_std::test]
+    async fn test_compress_streamed_body() {
+        let compression = Compression::new();
+        let pieces = vec![Ok(Bytes::from("hello ")), Ok(Bytes::from("world"))];
+        let response = Response {
+            body: Body::from_stream(futures::stream::iter(pieces)),
+            ..Response::default()
+        };
+        let response = compression.apply(Some(&accept("zstd")), response).await;
+        assert_eq!(
+            header(&response, HeaderType::ContentEncoding).unwrap(),
+            "zstd"
+        );
+        assert!(!response.headers.contains_key(&HeaderType::ContentLength));
+
+        let body = response.body.collect().await.unwrap();
+        let mut decoded = String::new();
+        ZstdDecoder::new(Cursor::new(body))
+            .read_to_string(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, "hello world");
+    }
+
+    #[async_std::test]
+    async fn test_bodies_left_alone() {
+        let compression = Compression::new();
+        let gzip = accept("gzip");
+
+        let small = compression
+            .apply(Some(&gzip), "small".into_response())
+            .await;
+        assert_eq!(header(&small, HeaderType::ContentEncoding), None);
+        assert_eq!(header(&small, HeaderType::Vary).unwrap(), "Accept-Encoding");
+        // unless identity is refused
+        let forced = compression
+            .apply(Some(&accept("gzip, identity;q=0")), "small".into_response())
+            .await;
+        assert_eq!(
+            header(&forced, HeaderType::ContentEncoding).unwrap(),
+            "gzip"
+        );
+
+        let image = text()
+            .into_response()
+            .with_header(ContentType(mime::IMAGE_PNG));
+        let image = compression.apply(Some(&gzip), image).await;
+        assert_eq!(header(&image, HeaderType::ContentEncoding), None);
+        assert_eq!(header(&image, HeaderType::Vary), None);
+
+        let unasked = compression.apply(None, text().into_response()).await;
+        assert_eq!(header(&unasked, HeaderType::ContentEncoding), None);
+
+        let refused = compression
+            .apply(Some(&accept("compress, *;q=0")), text().into_response())
+            .await;
+        assert_eq!(refused.status_line.status_code, 406);
+    }
+
+    #[async_std::test]
+    async fn test_vary_is_merged() {
+        let response = text()
+            .into_response()
+            .with_header(Vary::Headers(vec![HeaderType::Accept]));
+        let response = Compression::new()
+            .apply(Some(&accept("deflate")), response)
+            .await;
+        assert_eq!(
+            header(&response, HeaderType::Vary).unwrap(),
+            "Accept, Accept-Encoding"
+        );
+        assert_eq!(
+            header(&response, HeaderType::ContentEncoding).unwrap(),
+            "deflate"
+        );
+    }
+
+    async fn encoded(coding: ContentCoding, content: &[u8]) -> Bytes {
+        let mut encoded = Vec::new();
+        encoder(coding, Cursor::new(content.to_vec()))
+            .read_to_end(&mut encoded)
+            .await
+            .unwrap();
+        Bytes::from(encoded)
+    }
+
+    fn encoded_headers(encoding: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let encoding = HeaderValue::try_from(encoding).unwrap();
+        headers.append(HeaderType::ContentEncoding, encoding);
+        headers
+    }
+
+    #[async_std::test]
+    async fn test_decompress_request_body() {
+        let decompression = Decompression::new();
+        for coding in Decompression::CODINGS {
+            let mut headers = encoded_headers(coding.as_str());
+            let body = encoded(coding, b"{\"id\": 7}").await;
+            let decoded = decompression.apply(&mut headers, body).await.unwrap();
+            assert_eq!(decoded, "{\"id\": 7}");
+            assert!(!headers.contains_key(&HeaderType::ContentEncoding));
+            assert_eq!(
+                headers.typed_get::<ContentLength>().unwrap(),
+                Some(ContentLength(9))
+            );
+        }
+
+        // codings are undone in reverse order
+        let mut headers = encoded_headers("gzip, br");
+        let body = encoded(ContentCoding::Gzip, b"twice").await;
+        let body = encoded(ContentCoding::Brotli, &body).await;
+        assert_eq!(
+            decompression.apply(&mut headers, body).await.unwrap(),
+            "twice"
+        );
+
+        let mut headers = HeaderMap::new();
+        let plain = Bytes::from("plain");
+        assert_eq!(
+            decompression.apply(&mut headers, plain).await.unwrap(),
+            "plain"
+        );
+    }
+
+    #[async_std::test]
+    async fn test_decompress_rejects() {
+        let decompression = Decompression::new().max_length(1000);
+        let bomb = encoded(ContentCoding::Gzip, &[0; 100_000]).await;
+        assert!(bomb.len() < 1000);
+        let result = decompression
+            .apply(&mut encoded_headers("gzip"), bomb)
+            .await;
+        assert!(matches!(result, Err(ContentTooLarge(1000))));
+
+        let result = decompression
+            .apply(&mut encoded_headers("compress"), Bytes::from("x"))
+            .await;
+        assert!(matches!(result, Err(UnsupportedContentEncoding(coding)) if coding == "compress"));
+
+        let result = decompression
+            .apply(&mut encoded_headers("gzip"), Bytes::from("not gzip"))
+            .await;
+        assert!(matches!(result, Err(ParsingRequestErr(_))));
+    }
+}
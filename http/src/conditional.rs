@@ -0,0 +1,288 @@
+use crate::{
+    body::Body,
+    date::HttpDate,
+    headers::{
+        ETag, EntityTag, EntityTagList, Header, HeaderMap, HeaderType, IfMatch, IfModifiedSince,
+        IfNoneMatch, IfUnmodifiedSince, LastModified,
+    },
+    method::Method,
+    response::{IntoResponse, Response},
+    status_code::Status,
+};
+
+/// Representation metadata a 304 does not repeat, RFC 9110 section 15.4.5.
+const REPRESENTATION_HEADERS: [HeaderType; 6] = [
+    HeaderType::ContentType,
+    HeaderType::ContentLength,
+    HeaderType::ContentEncoding,
+    HeaderType::ContentLanguage,
+    HeaderType::ContentRange,
+    HeaderType::TransferEncoding,
+];
+
+/// Validators of the current representation of a resource.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<EntityTag>,
+    pub last_modified: Option<HttpDate>,
+}
+
+impl Validators {
+    /// The `ETag` and `Last-Modified` of a response, invalid ones are ignored.
+    pub fn of(headers: &HeaderMap) -> Self {
+        Self {
+            etag: headers
+                .typed_get::<ETag>()
+                .ok()
+                .flatten()
+                .map(|etag| etag.0),
+            last_modified: headers
+                .typed_get::<LastModified>()
+                .ok()
+                .flatten()
+                .map(|last_modified| last_modified.0),
+        }
+    }
+}
+
+/// Outcome of evaluating the preconditions of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// Every condition holds, the request is handled as usual.
+    Proceed,
+    /// The cached representation of the client is still current.
+    NotModified,
+    /// A condition failed, answered with `412 Precondition Failed`.
+    Failed,
+}
+
+/// Conditional headers of a request, `If-Match`, `If-None-Match`,
+/// `If-Modified-Since` and `If-Unmodified-Since`. Invalid ones are ignored.
+///
+/// The router evaluates them for `GET` and `HEAD` against the `ETag` and
+/// `Last-Modified` of the response. Handlers of other methods evaluate them
+/// before changing anything, see [`Preconditions::evaluate`].
+#[derive(Debug, Clone, Default)]
+pub struct Preconditions {
+    method: Method,
+    if_match: Option<IfMatch>,
+    if_none_match: Option<IfNoneMatch>,
+    if_modified_since: Option<HttpDate>,
+    if_unmodified_since: Option<HttpDate>,
+}
+
+impl Preconditions {
+    pub fn from_request(method: Method, headers: &HeaderMap) -> Self {
+        fn header<H: Header>(headers: &HeaderMap) -> Option<H> {
+            headers.typed_get::<H>().ok().flatten()
+        }
+        Self {
+            method,
+            if_match: header(headers),
+            if_none_match: header(headers),
+            if_modified_since: header::<IfModifiedSince>(headers).map(|since| since.0),
+            if_unmodified_since: header::<IfUnmodifiedSince>(headers).map(|since| since.0),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none()
+            && self.if_none_match.is_none()
+            && self.if_modified_since.is_none()
+            && self.if_unmodified_since.is_none()
+    }
+
+    /// Evaluates the conditions in the order of RFC 9110 section 13.2.2
+    /// against the `current` representation, `None` when there is none.
+    pub fn evaluate(&self, current: Option<&Validators>) -> Precondition {
+        let exists = current.is_some();
+        let etag = current.and_then(|current| current.etag.as_ref());
+        let last_modified = current.and_then(|current| current.last_modified);
+        let safe = matches!(self.method, Method::Get | Method::Head);
+
+        if let Some(if_match) = &self.if_match {
+            let matched = match (&if_match.0, etag) {
+                (EntityTagList::Any, _) => exists,
+                (EntityTagList::Tags(_), Some(etag)) => if_match.matches(etag),
+                (EntityTagList::Tags(_), None) => false,
+            };
+            if !matched {
+                return Precondition::Failed;
+            }
+        } else if let (Some(since), Some(last_modified)) = (self.if_unmodified_since, last_modified)
+        {
+            if last_modified > since {
+                return Precondition::Failed;
+            }
+        }
+
+        if let Some(if_none_match) = &self.if_none_match {
+            let matched = match (&if_none_match.0, etag) {
+                (EntityTagList::Any, _) => exists,
+                (EntityTagList::Tags(_), Some(etag)) => if_none_match.matches(etag),
+                (EntityTagList::Tags(_), None) => false,
+            };
+            if matched {
+                return match safe {
+                    true => Precondition::NotModified,
+                    false => Precondition::Failed,
+                };
+            }
+        } else if let (true, Some(since), Some(last_modified)) =
+            (safe, self.if_modified_since, last_modified)
+        {
+            if last_modified <= since {
+                return Precondition::NotModified;
+            }
+        }
+        Precondition::Proceed
+    }
+
+    /// Evaluates the conditions against the validators of a successful
+    /// response, turning it into a 304 without body or a 412.
+    pub fn apply<'rs>(&self, response: Response<'rs>) -> Response<'rs> {
+        let status = response.status_line.status_code;
+        if self.is_empty() || !(200..300).contains(&status) {
+            return response;
+        }
+        match self.evaluate(Some(&Validators::of(&response.headers))) {
+            Precondition::Proceed => response,
+            Precondition::NotModified => not_modified(response),
+            Precondition::Failed => Status::PreconditionFailed.into_response(),
+        }
+    }
+}
+
+fn not_modified(mut response: Response<'_>) -> Response<'_> {
+    response.add_status(Status::NotModified);
+    response
+        .headers
+        .retain(|header_type, _| !REPRESENTATION_HEADERS.contains(header_type));
+    response.body = Body::empty();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::HeaderValue;
+
+    fn preconditions(method: Method, headers: &[(HeaderType, &str)]) -> Preconditions {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name.clone(), HeaderValue::try_from(*value).unwrap());
+        }
+        Preconditions::from_request(method, &map)
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let modified = HttpDate::from_timestamp(784_111_777);
+        let current = Validators {
+            etag: Some(EntityTag::strong("v2").unwrap()),
+            last_modified: Some(modified),
+        };
+        let evaluate = |method, headers: &[(HeaderType, &str)]| {
+            preconditions(method, headers).evaluate(Some(&current))
+        };
+        let before = "Sun, 06 Nov 1994 08:49:36 GMT";
+        let at = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+        assert_eq!(evaluate(Method::Get, &[]), Precondition::Proceed);
+        assert_eq!(
+            evaluate(
+                Method::Get,
+                &[(HeaderType::IfNoneMatch, "\"v1\", W/\"v2\"")]
+            ),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate(Method::Put, &[(HeaderType::IfNoneMatch, "*")]),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(Method::Put, &[(HeaderType::IfMatch, "W/\"v2\"")]),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(Method::Put, &[(HeaderType::IfMatch, "\"v2\"")]),
+            Precondition::Proceed
+        );
+        assert_eq!(
+            evaluate(Method::Get, &[(HeaderType::IfModifiedSince, at)]),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate(Method::Get, &[(HeaderType::IfModifiedSince, before)]),
+            Precondition::Proceed
+        );
+        // If-None-Match takes precedence over If-Modified-Since
+        assert_eq!(
+            evaluate(
+                Method::Get,
+                &[
+                    (HeaderType::IfNoneMatch, "\"v1\""),
+                    (HeaderType::IfModifiedSince, at)
+                ]
+            ),
+            Precondition::Proceed
+        );
+        // If-Match takes precedence over If-Unmodified-Since
+        assert_eq!(
+            evaluate(
+                Method::Delete,
+                &[
+                    (HeaderType::IfMatch, "*"),
+                    (HeaderType::IfUnmodifiedSince, before)
+                ]
+            ),
+            Precondition::Proceed
+        );
+        assert_eq!(
+            evaluate(Method::Delete, &[(HeaderType::IfUnmodifiedSince, before)]),
+            Precondition::Failed
+        );
+        // an invalid date is ignored
+        assert_eq!(
+            evaluate(Method::Get, &[(HeaderType::IfModifiedSince, "yesterday")]),
+            Precondition::Proceed
+        );
+
+        let create = preconditions(Method::Put, &[(HeaderType::IfNoneMatch, "*")]);
+        assert_eq!(create.evaluate(None), Precondition::Proceed);
+        let update = preconditions(Method::Put, &[(HeaderType::IfMatch, "*")]);
+        assert_eq!(update.evaluate(None), Precondition::Failed);
+    }
+
+    #[test]
+    fn test_apply_to_response() {
+        let response = || {
+            "hello"
+                .into_response()
+                .with_etag()
+                .with_header(LastModified(HttpDate::UNIX_EPOCH))
+        };
+        let etag = response().headers.typed_get::<ETag>().unwrap().unwrap().0;
+        assert!(!etag.is_weak());
+
+        let conditions =
+            preconditions(Method::Get, &[(HeaderType::IfNoneMatch, &etag.to_string())]);
+        let not_modified = conditions.apply(response());
+        assert_eq!(not_modified.status_line.status_code, 304);
+        assert_eq!(not_modified.body, "");
+        assert!(!not_modified
+            .headers
+            .contains_key(&HeaderType::ContentLength));
+        assert!(not_modified.headers.contains_key(&HeaderType::ETag));
+        assert!(not_modified.headers.contains_key(&HeaderType::LastModified));
+
+        let conditions = preconditions(Method::Get, &[(HeaderType::IfMatch, "\"other\"")]);
+        let failed = conditions.apply(response());
+        assert_eq!(failed.status_line.status_code, 412);
+        assert_eq!(failed.body, "");
+
+        let conditions = preconditions(Method::Get, &[(HeaderType::IfNoneMatch, "*")]);
+        let missing = conditions.apply(Status::NotFound.into_response());
+        assert_eq!(missing.status_line.status_code, 404);
+    }
+}
@@ -0,0 +1,433 @@
+use crate::{
+    common::RhttpError::{self, InvalidCookie},
+    date::HttpDate,
+    headers::{HeaderMap, HeaderType, HeaderValue},
+};
+use chrono::{DateTime, Utc};
+use std::{fmt::Formatter, time::Duration};
+
+mod secure;
+
+pub use secure::{CookieKeys, Key, PrivateCookieJar, SignedCookieJar};
+
+/// Cross-site policy of a cookie, RFC 6265bis section 4.1.2.7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Browsers only accept it together with `Secure`.
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// A cookie with the attributes of a `Set-Cookie` header, RFC 6265.
+///
+/// Cookies received in a request header only carry a name and a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    expires: Option<DateTime<Utc>>,
+    max_age: Option<Duration>,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    partitioned: bool,
+}
+
+impl Cookie {
+    /// A cookie without attributes, see [`Cookie::builder`] for the others.
+    pub fn new(name: &str, value: &str) -> Result<Self, RhttpError> {
+        Self::builder(name, value).build()
+    }
+
+    pub fn builder(name: &str, value: &str) -> CookieBuilder {
+        CookieBuilder {
+            cookie: Self::unchecked(name, value),
+        }
+    }
+
+    /// Builds a cookie that makes the client drop `name`, the path and
+    /// domain have to be the ones it was set with.
+    pub fn removal(name: &str) -> CookieBuilder {
+        Self::builder(name, "")
+            .max_age(Duration::ZERO)
+            .expires(DateTime::UNIX_EPOCH)
+    }
+
+    fn unchecked(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            expires: None,
+            max_age: None,
+            domain: None,
+            path: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            partitioned: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn expires(&self) -> Option<DateTime<Utc>> {
+        self.expires
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn is_http_only(&self) -> bool {
+        self.http_only
+    }
+
+    pub fn same_site(&self) -> Option<SameSite> {
+        self.same_site
+    }
+
+    pub fn is_partitioned(&self) -> bool {
+        self.partitioned
+    }
+
+    /// Parses a `Set-Cookie` value, unknown or malformed attributes are
+    /// ignored as RFC 6265 section 5.2 asks.
+    pub fn parse(set_cookie: &str) -> Result<Self, RhttpError> {
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts
+            .next()
+            .and_then(|pair| pair.split_once('='))
+            .ok_or_else(|| InvalidCookie(set_cookie.to_owned()))?;
+        let mut builder = Self::builder(name.trim(), unquote(value.trim()));
+        for attribute in parts {
+            let (name, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let (name, value) = (name.trim(), value.trim());
+            builder = match name.to_ascii_lowercase().as_str() {
+                "expires" => match value.parse::<HttpDate>() {
+                    Ok(expires) => builder.expires(expires.into()),
+                    Err(_) => builder,
+                },
+                // a negative Max-Age expires the cookie right away
+                "max-age" => match value.parse::<i64>() {
+                    Ok(seconds) => builder.max_age(Duration::from_secs(seconds.max(0) as u64)),
+                    Err(_) => builder,
+                },
+                "domain" if !value.is_empty() => builder.domain(value.trim_start_matches('.')),
+                "path" if value.starts_with('/') => builder.path(value),
+                "secure" => builder.secure(true),
+                "httponly" => builder.http_only(true),
+                "samesite" => match value.to_ascii_lowercase().as_str() {
+                    "strict" => builder.same_site(SameSite::Strict),
+                    "lax" => builder.same_site(SameSite::Lax),
+                    "none" => builder.same_site(SameSite::None),
+                    _ => builder,
+                },
+                "partitioned" => builder.partitioned(true),
+                _ => builder,
+            };
+        }
+        builder.build()
+    }
+
+    /// The `Set-Cookie` value of the cookie.
+    pub fn encode(&self) -> HeaderValue {
+        HeaderValue::from_encoded(self.to_string())
+    }
+}
+
+impl std::fmt::Display for Cookie {
+    /// Formats the cookie as a `Set-Cookie` value.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", HttpDate::from(expires))?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={domain}")?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={path}")?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        if self.partitioned {
+            f.write_str("; Partitioned")?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`Cookie`], `build` checks the name is a token and the value and
+/// attributes cannot break out of the `Set-Cookie` header.
+#[derive(Debug, Clone)]
+pub struct CookieBuilder {
+    cookie: Cookie,
+}
+
+impl CookieBuilder {
+    pub fn expires(mut self, expires: DateTime<Utc>) -> Self {
+        self.cookie.expires = Some(expires);
+        self
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.cookie.max_age = Some(max_age);
+        self
+    }
+
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.cookie.domain = Some(domain.to_owned());
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.cookie.path = Some(path.to_owned());
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.cookie.secure = secure;
+        self
+    }
+
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.cookie.http_only = http_only;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.cookie.same_site = Some(same_site);
+        self
+    }
+
+    /// Partitioned cookies (CHIPS) also have to be `Secure`.
+    pub fn partitioned(mut self, partitioned: bool) -> Self {
+        self.cookie.partitioned = partitioned;
+        self
+    }
+
+    pub fn build(self) -> Result<Cookie, RhttpError> {
+        let cookie = self.cookie;
+        let attribute_valid = |value: &Option<String>| {
+            value.as_deref().is_none_or(|value| {
+                value
+                    .bytes()
+                    .all(|b| (b' '..=b'~').contains(&b) && b != b';')
+            })
+        };
+        if !crate::headers::is_token(&cookie.name)
+            || !is_cookie_value(&cookie.value)
+            || !attribute_valid(&cookie.domain)
+            || !attribute_valid(&cookie.path)
+        {
+            return Err(InvalidCookie(cookie.name));
+        }
+        Ok(cookie)
+    }
+}
+
+/// Cookies of a request with the changes a handler made to them.
+///
+/// Inserted and removed cookies are sent back as one `Set-Cookie` header each
+/// when the jar is returned along with the response, `(jar, response)`.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+    delta: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cookies of every `Cookie` header, pairs that are not `name=value` are
+    /// skipped.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let cookies = headers
+            .get_all(&HeaderType::Cookie)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, value)| Cookie::unchecked(name.trim(), unquote(value.trim())))
+            .collect();
+        Self {
+            cookies,
+            delta: Vec::new(),
+        }
+    }
+
+    /// The cookie called `name`, as changed by this jar.
+    pub fn get(&self, name: &str) -> Option<&Cookie> {
+        match self.delta.iter().rfind(|cookie| cookie.name == name) {
+            Some(cookie) if is_removal(cookie) => None,
+            Some(cookie) => Some(cookie),
+            None => self.cookies.iter().find(|cookie| cookie.name == name),
+        }
+    }
+
+    pub fn insert(mut self, cookie: Cookie) -> Self {
+        self.delta.retain(|added| !same_cookie(added, &cookie));
+        self.delta.push(cookie);
+        self
+    }
+
+    /// Removes the cookie from the client, its path and domain are kept
+    /// since the client only drops a cookie when they match.
+    pub fn remove(self, cookie: Cookie) -> Self {
+        let mut removal = Cookie::removal(&cookie.name);
+        if let Some(path) = &cookie.path {
+            removal = removal.path(path);
+        }
+        if let Some(domain) = &cookie.domain {
+            removal = removal.domain(domain);
+        }
+        match removal.build() {
+            Ok(removal) => self.insert(removal),
+            Err(_) => self,
+        }
+    }
+
+    /// Cookies sent with the request.
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.cookies.iter()
+    }
+
+    /// Cookies inserted or removed, in the order of the changes.
+    pub fn delta(&self) -> impl Iterator<Item = &Cookie> {
+        self.delta.iter()
+    }
+}
+
+fn is_removal(cookie: &Cookie) -> bool {
+    cookie.max_age == Some(Duration::ZERO)
+}
+
+/// The client keys a cookie by its name, domain and path.
+fn same_cookie(a: &Cookie, b: &Cookie) -> bool {
+    a.name == b.name && a.domain == b.domain && a.path == b.path
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// `cookie-value`, RFC 6265 section 4.1.1.
+fn is_cookie_value(value: &str) -> bool {
+    unquote(value)
+        .bytes()
+        .all(|b| matches!(b, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_cookie_round_trip() {
+        let cookie = Cookie::builder("session", "abc123")
+            .expires(DateTime::from_timestamp(784_111_777, 0).unwrap())
+            .max_age(Duration::from_secs(3600))
+            .domain("example.test")
+            .path("/")
+            .secure(true)
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .partitioned(true)
+            .build()
+            .unwrap();
+        let set_cookie = cookie.to_string();
+        assert_eq!(
+            set_cookie,
+            "session=abc123; Expires=Sun, 06 Nov 1994 08:49:37 GMT; Max-Age=3600; \
+             Domain=example.test; Path=/; Secure; HttpOnly; SameSite=Lax; Partitioned"
+        );
+        assert_eq!(Cookie::parse(&set_cookie).unwrap(), cookie);
+
+        let parsed =
+            Cookie::parse("id=\"x\"; max-age=-5; domain=.example.test; path=rel; unknown").unwrap();
+        assert_eq!(parsed.value(), "x");
+        assert_eq!(parsed.max_age(), Some(Duration::ZERO));
+        assert_eq!(parsed.domain(), Some("example.test"));
+        assert_eq!(parsed.path(), None);
+    }
+
+    #[test]
+    fn test_invalid_cookies_are_rejected() {
+        assert!(Cookie::new("a b", "1").is_err());
+        assert!(Cookie::new("a", "1; Domain=evil.test").is_err());
+        assert!(Cookie::new("a", "1,2").is_err());
+        assert!(Cookie::builder("a", "1").path("/; Secure").build().is_err());
+        assert!(Cookie::parse("no-pair").is_err());
+    }
+
+    #[test]
+    fn test_cookie_jar() {
+        let mut headers = HeaderMap::new();
+        headers.append(HeaderType::Cookie, "a=1; b=\"2\"; junk".parse().unwrap());
+        headers.append(HeaderType::Cookie, "c=3".parse().unwrap());
+        let jar = CookieJar::from_headers(&headers);
+        let names: Vec<&str> = jar.iter().map(Cookie::value).collect();
+        assert_eq!(names, ["1", "2", "3"]);
+
+        let jar = jar
+            .insert(Cookie::new("d", "4").unwrap())
+            .remove(Cookie::builder("a", "").path("/").build().unwrap())
+            .insert(Cookie::new("d", "5").unwrap());
+        assert_eq!(jar.get("a"), None);
+        assert_eq!(jar.get("b").map(Cookie::value), Some("2"));
+        assert_eq!(jar.get("d").map(Cookie::value), Some("5"));
+        let delta: Vec<String> = jar.delta().map(Cookie::to_string).collect();
+        assert_eq!(
+            delta,
+            [
+                "a=; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0; Path=/",
+                "d=5"
+            ]
+        );
+    }
+}
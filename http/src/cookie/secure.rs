@@ -0,0 +1,291 @@
+use super::{Cookie, CookieJar};
+use crate::common::RhttpError::{self, InvalidCookieKey};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{fmt::Formatter, sync::Arc};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 12;
+
+/// Secret the signed and private cookie jars derive their keys from.
+#[derive(Clone)]
+pub struct Key {
+    signing: [u8; 32],
+    encryption: [u8; 32],
+}
+
+impl Key {
+    /// Derives the keys from `secret`, which has to be at least 32 random bytes.
+    pub fn from_secret(secret: &[u8]) -> Result<Self, RhttpError> {
+        if secret.len() < 32 {
+            return Err(InvalidCookieKey);
+        }
+        let derive = |purpose: &[u8]| -> [u8; 32] {
+            let mut mac =
+                <HmacSha256 as Mac>::new_from_slice(secret).expect("HMAC takes keys of any size");
+            mac.update(purpose);
+            mac.finalize().into_bytes().into()
+        };
+        Ok(Self {
+            signing: derive(b"cookie signing"),
+            encryption: derive(b"cookie encryption"),
+        })
+    }
+
+    fn mac(&self, name: &str, value: &str) -> HmacSha256 {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.signing)
+            .expect("HMAC takes keys of any size");
+        mac.update(name.as_bytes());
+        mac.update(b"=");
+        mac.update(value.as_bytes());
+        mac
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.encryption.into())
+    }
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// Keys of the signed and private jars. Cookies are signed and encrypted with
+/// the current key, the previous ones are still accepted so the secret can be
+/// rotated without logging everybody out.
+#[derive(Debug, Clone)]
+pub struct CookieKeys {
+    current: Key,
+    previous: Vec<Key>,
+}
+
+impl CookieKeys {
+    pub fn new(current: Key) -> Self {
+        Self {
+            current,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Keeps accepting cookies made with a retired key.
+    pub fn accept(mut self, previous: Key) -> Self {
+        self.previous.push(previous);
+        self
+    }
+
+    fn all(&self) -> impl Iterator<Item = &Key> {
+        std::iter::once(&self.current).chain(&self.previous)
+    }
+}
+
+/// A [`CookieJar`] whose cookies carry an HMAC-SHA256 tag of their name and
+/// value, the client can read them but not change them.
+#[derive(Debug, Clone)]
+pub struct SignedCookieJar {
+    jar: CookieJar,
+    keys: Arc<CookieKeys>,
+}
+
+impl SignedCookieJar {
+    pub fn new(jar: CookieJar, keys: Arc<CookieKeys>) -> Self {
+        Self { jar, keys }
+    }
+
+    /// The cookie with the tag stripped, `None` when it is missing or its
+    /// tag matches none of the keys.
+    pub fn get(&self, name: &str) -> Option<Cookie> {
+        let cookie = self.jar.get(name)?;
+        let (tag, value) = cookie.value.split_once('.')?;
+        let tag = URL_SAFE_NO_PAD.decode(tag).ok()?;
+        self.keys
+            .all()
+            .any(|key| key.mac(name, value).verify_slice(&tag).is_ok())
+            .then(|| Cookie {
+                value: value.to_owned(),
+                ..cookie.clone()
+            })
+    }
+
+    pub fn insert(mut self, mut cookie: Cookie) -> Self {
+        let tag = self.keys.current.mac(&cookie.name, &cookie.value);
+        let tag = URL_SAFE_NO_PAD.encode(tag.finalize().into_bytes());
+        cookie.value = format!("{tag}.{}", cookie.value);
+        self.jar = self.jar.insert(cookie);
+        self
+    }
+
+    pub fn remove(mut self, cookie: Cookie) -> Self {
+        self.jar = self.jar.remove(cookie);
+        self
+    }
+}
+
+impl From<SignedCookieJar> for CookieJar {
+    fn from(signed: SignedCookieJar) -> Self {
+        signed.jar
+    }
+}
+
+/// A [`CookieJar`] whose cookie values are encrypted with AES-256-GCM, the
+/// client can neither read nor change them. The name is authenticated as well,
+/// a value cannot be moved to another cookie.
+#[derive(Debug, Clone)]
+pub struct PrivateCookieJar {
+    jar: CookieJar,
+    keys: Arc<CookieKeys>,
+}
+
+impl PrivateCookieJar {
+    pub fn new(jar: CookieJar, keys: Arc<CookieKeys>) -> Self {
+        Self { jar, keys }
+    }
+
+    /// The cookie with its value decrypted, `None` when it is missing or
+    /// none of the keys decrypts it.
+    pub fn get(&self, name: &str) -> Option<Cookie> {
+        let cookie = self.jar.get(name)?;
+        let sealed = URL_SAFE_NO_PAD.decode(&cookie.value).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = || Payload {
+            msg: ciphertext,
+            aad: name.as_bytes(),
+        };
+        let value = self.keys.all().find_map(|key| {
+            key.cipher()
+                .decrypt(Nonce::from_slice(nonce), payload())
+                .ok()
+        })?;
+        Some(Cookie {
+            value: String::from_utf8(value).ok()?,
+            ..cookie.clone()
+        })
+    }
+
+    pub fn insert(mut self, mut cookie: Cookie) -> Self {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: cookie.value.as_bytes(),
+            aad: cookie.name.as_bytes(),
+        };
+        let ciphertext = self
+            .keys
+            .current
+            .cipher()
+            .encrypt(&nonce, payload)
+            .expect("cookie values fit AES-GCM");
+        cookie.value = URL_SAFE_NO_PAD.encode([nonce.as_slice(), &ciphertext].concat());
+        self.jar = self.jar.insert(cookie);
+        self
+    }
+
+    pub fn remove(mut self, cookie: Cookie) -> Self {
+        self.jar = self.jar.remove(cookie);
+        self
+    }
+}
+
+impl From<PrivateCookieJar> for CookieJar {
+    fn from(private: PrivateCookieJar) -> Self {
+        private.jar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::{HeaderMap, HeaderType};
+
+    fn keys(secret: u8) -> Arc<CookieKeys> {
+        Arc::new(CookieKeys::new(Key::from_secret(&[secret; 32]).unwrap()))
+    }
+
+    /// The jar a client sending back the cookies of `jar` would produce.
+    fn round_trip(jar: CookieJar) -> CookieJar {
+        let mut headers = HeaderMap::new();
+        for cookie in jar.delta() {
+            let pair = format!("{}={}", cookie.name(), cookie.value());
+            headers.append(HeaderType::Cookie, pair.parse().unwrap());
+        }
+        CookieJar::from_headers(&headers)
+    }
+
+    fn tampered(jar: &CookieJar, name: &str) -> CookieJar {
+        let cookie = jar.get(name).unwrap();
+        let mut value = cookie.value().to_owned();
+        let last = if value.ends_with('A') { "B" } else { "A" };
+        value.replace_range(value.len() - 1.., last);
+        CookieJar::new().insert(Cookie::new(name, &value).unwrap())
+    }
+
+    #[test]
+    fn test_signed_jar() {
+        let signed = SignedCookieJar::new(CookieJar::new(), keys(1))
+            .insert(Cookie::new("user", "42").unwrap());
+        let received = round_trip(signed.into());
+        assert!(received.get("user").unwrap().value().ends_with(".42"));
+
+        let signed = SignedCookieJar::new(received.clone(), keys(1));
+        assert_eq!(
+            signed.get("user").map(|cookie| cookie.value().to_owned()),
+            Some(String::from("42"))
+        );
+        assert_eq!(
+            SignedCookieJar::new(received.clone(), keys(2)).get("user"),
+            None
+        );
+        assert_eq!(
+            SignedCookieJar::new(tampered(&received, "user"), keys(1)).get("user"),
+            None
+        );
+
+        let rotated = CookieKeys::new(Key::from_secret(&[2; 32]).unwrap())
+            .accept(Key::from_secret(&[1; 32]).unwrap());
+        assert!(SignedCookieJar::new(received, Arc::new(rotated))
+            .get("user")
+            .is_some());
+    }
+
+    #[test]
+    fn test_private_jar() {
+        let private = PrivateCookieJar::new(CookieJar::new(), keys(1))
+            .insert(Cookie::new("hint", "cart=3").unwrap());
+        let received = round_trip(private.into());
+        assert!(!received.get("hint").unwrap().value().contains("cart"));
+
+        let private = PrivateCookieJar::new(received.clone(), keys(1));
+        assert_eq!(private.get("hint").unwrap().value(), "cart=3");
+        assert_eq!(
+            PrivateCookieJar::new(received.clone(), keys(2)).get("hint"),
+            None
+        );
+        assert_eq!(
+            PrivateCookieJar::new(tampered(&received, "hint"), keys(1)).get("hint"),
+            None
+        );
+
+        // the name is authenticated, a value does not decrypt under another one
+        let value = received.get("hint").unwrap().value().to_owned();
+        let moved = CookieJar::new().insert(Cookie::new("other", &value).unwrap());
+        assert_eq!(PrivateCookieJar::new(moved, keys(1)).get("other"), None);
+    }
+
+    #[test]
+    fn test_short_secret_is_rejected() {
+        assert!(matches!(Key::from_secret(b"short"), Err(InvalidCookieKey)));
+        assert_eq!(
+            format!("{:?}", Key::from_secret(&[0; 32]).unwrap()),
+            "Key(..)"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::common::RhttpError::{self, InvalidHeader};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use std::{fmt::Formatter, str::FromStr, time::SystemTime};
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const LONG_DAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+/// The first second of year 0 and the last one of year 9999, IMF-fixdate
+/// has four digits for the year.
+const MIN_TIMESTAMP: i64 = -62_167_219_200;
+const MAX_TIMESTAMP: i64 = 253_402_300_799;
+
+/// A timestamp as HTTP carries it, RFC 9110 section 5.6.7. Precise to the
+/// second, formatted as IMF-fixdate, so limited to the years 0 to 9999.
+///
+/// Parsing accepts the obsolete RFC 850 and asctime forms as well, a
+/// mismatching day name is ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HttpDate(DateTime<Utc>);
+
+impl HttpDate {
+    pub const UNIX_EPOCH: Self = Self(DateTime::UNIX_EPOCH);
+
+    pub fn now() -> Self {
+        Self::from(Utc::now())
+    }
+
+    /// A timestamp before year 0 or after year 9999 is clamped to the
+    /// first or the last second IMF-fixdate can express.
+    pub fn from_timestamp(seconds: i64) -> Self {
+        let seconds = seconds.clamp(MIN_TIMESTAMP, MAX_TIMESTAMP);
+        Self(DateTime::from_timestamp(seconds, 0).expect("years 0 to 9999 are representable"))
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.0.timestamp()
+    }
+
+    pub fn to_datetime(self) -> DateTime<Utc> {
+        self.0
+    }
+
+    fn parse(date: &str) -> Option<Self> {
+        let date = parse_imf_fixdate(date)
+            .or_else(|| parse_rfc850(date, Utc::now().year()))
+            .or_else(|| parse_asctime(date))?;
+        Some(Self(date.and_utc()))
+    }
+}
+
+impl From<DateTime<Utc>> for HttpDate {
+    /// Drops the fraction of the second, clamps the year as
+    /// [`HttpDate::from_timestamp`] does.
+    fn from(date: DateTime<Utc>) -> Self {
+        Self::from_timestamp(date.timestamp())
+    }
+}
+
+impl From<SystemTime> for HttpDate {
+    fn from(time: SystemTime) -> Self {
+        Self::from(DateTime::<Utc>::from(time))
+    }
+}
+
+impl From<HttpDate> for DateTime<Utc> {
+    fn from(date: HttpDate) -> Self {
+        date.0
+    }
+}
+
+impl FromStr for HttpDate {
+    type Err = RhttpError;
+
+    fn from_str(date: &str) -> Result<Self, Self::Err> {
+        Self::parse(date).ok_or_else(|| InvalidHeader(format!("HTTP date {date:?}")))
+    }
+}
+
+impl std::fmt::Display for HttpDate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let date = self.0;
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            DAYS[date.weekday().num_days_from_monday() as usize],
+            date.day(),
+            MONTHS[date.month0() as usize],
+            date.year(),
+            date.hour(),
+            date.minute(),
+            date.second(),
+        )
+    }
+}
+
+/// Exactly `digits` ASCII digits.
+fn number(value: &str, digits: usize) -> Option<u32> {
+    (value.len() == digits && value.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| value.parse().ok())
+        .flatten()
+}
+
+fn month(name: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|month| *month == name)
+        .map(|month| month as u32 + 1)
+}
+
+/// `hh:mm:ss`
+fn time(time: &str) -> Option<NaiveTime> {
+    let mut parts = time.split(':');
+    let (hour, minute, second) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    NaiveTime::from_hms_opt(number(hour, 2)?, number(minute, 2)?, number(second, 2)?)
+}
+
+fn date_time(year: i32, month: u32, day: u32, time: NaiveTime) -> Option<NaiveDateTime> {
+    Some(NaiveDate::from_ymd_opt(year, month, day)?.and_time(time))
+}
+
+/// `Sun, 06 Nov 1994 08:49:37 GMT`
+fn parse_imf_fixdate(date: &str) -> Option<NaiveDateTime> {
+    let (day_name, rest) = date.split_once(", ")?;
+    if !DAYS.contains(&day_name) {
+        return None;
+    }
+    match rest.split(' ').collect::<Vec<&str>>()[..] {
+        [day, month_name, year, clock, "GMT"] => date_time(
+            number(year, 4)? as i32,
+            month(month_name)?,
+            number(day, 2)?,
+            time(clock)?,
+        ),
+        _ => None,
+    }
+}
+
+/// `Sunday, 06-Nov-94 08:49:37 GMT`, a year more than 50 years after
+/// `current_year` lies in the previous century, RFC 9110 section 5.6.7.
+fn parse_rfc850(date: &str, current_year: i32) -> Option<NaiveDateTime> {
+    let (day_name, rest) = date.split_once(", ")?;
+    if !LONG_DAYS.contains(&day_name) {
+        return None;
+    }
+    let [day_month_year, clock, "GMT"] = rest.split(' ').collect::<Vec<&str>>()[..] else {
+        return None;
+    };
+    let [day, month_name, year] = day_month_year.split('-').collect::<Vec<&str>>()[..] else {
+        return None;
+    };
+    let mut year = current_year - current_year % 100 + number(year, 2)? as i32;
+    if year > current_year + 50 {
+        year -= 100;
+    }
+    date_time(year, month(month_name)?, number(day, 2)?, time(clock)?)
+}
+
+/// `Sun Nov  6 08:49:37 1994`, the day is padded with a space.
+fn parse_asctime(date: &str) -> Option<NaiveDateTime> {
+    let separated = [3, 7, 10, 19]
+        .iter()
+        .all(|at| date.as_bytes().get(*at) == Some(&b' '));
+    if date.len() != 24 || !separated || !DAYS.contains(&date.get(..3)?) {
+        return None;
+    }
+    let day = date.get(8..10)?;
+    let day = match day.strip_prefix(' ') {
+        Some(digit) => number(digit, 1)?,
+        None => number(day, 2)?,
+    };
+    date_time(
+        number(date.get(20..)?, 4)? as i32,
+        month(date.get(4..7)?)?,
+        day,
+        time(date.get(11..19)?)?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_all_forms() {
+        let expected = HttpDate::from_timestamp(784_111_777);
+        for date in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            // the day name is not checked against the date
+            "Mon, 06 Nov 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(date.parse::<HttpDate>().unwrap(), expected, "{date}");
+        }
+        assert_eq!(expected.to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        for date in [
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 31 Feb 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun Nov 6 08:49:37 1994",
+            "Sunday, 06-Nov-1994 08:49:37 GMT",
+            "1994-11-06T08:49:37Z",
+        ] {
+            assert!(date.parse::<HttpDate>().is_err(), "{date}");
+        }
+    }
+
+    #[test]
+    fn test_rfc850_century() {
+        let year = |date| parse_rfc850(date, 2026).unwrap().year();
+        assert_eq!(year("Sunday, 06-Nov-94 08:49:37 GMT"), 1994);
+        assert_eq!(year("Monday, 06-Nov-76 08:49:37 GMT"), 2076);
+        assert_eq!(year("Monday, 06-Nov-77 08:49:37 GMT"), 1977);
+        assert_eq!(year("Monday, 06-Nov-26 08:49:37 GMT"), 2026);
+    }
+
+    #[test]
+    fn test_years_outside_imf_fixdate_are_clamped() {
+        let first = "Sat, 01 Jan 0000 00:00:00 GMT";
+        let last = "Fri, 31 Dec 9999 23:59:59 GMT";
+        assert_eq!(HttpDate::from_timestamp(MIN_TIMESTAMP).to_string(), first);
+        assert_eq!(HttpDate::from_timestamp(MAX_TIMESTAMP).to_string(), last);
+        assert_eq!(HttpDate::from_timestamp(i64::MIN).to_string(), first);
+        assert_eq!(HttpDate::from_timestamp(i64::MAX).to_string(), last);
+
+        let ancient = NaiveDate::from_ymd_opt(-44, 3, 15)
+            .unwrap()
+            .and_time(NaiveTime::MIN);
+        assert_eq!(HttpDate::from(ancient.and_utc()).to_string(), first);
+        let future = NaiveDate::from_ymd_opt(12_000, 1, 1)
+            .unwrap()
+            .and_time(NaiveTime::MIN);
+        assert_eq!(HttpDate::from(future.and_utc()).to_string(), last);
+        assert_eq!(last.parse::<HttpDate>().unwrap().timestamp(), MAX_TIMESTAMP);
+    }
+}
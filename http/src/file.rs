@@ -0,0 +1,186 @@
+use crate::{
+    body::Body,
+    common::RhttpError,
+    conditional::Validators,
+    date::HttpDate,
+    framing::STREAM_CHUNK_LENGTH,
+    headers::{AcceptRanges, ContentLength, ContentType, ETag, EntityTag, LastModified},
+    range::{partial, unsatisfiable, Piece, RangeRequest, Ranges},
+    response::{IntoResponse, Response},
+};
+use async_std::{fs::File, path::Path};
+use bytes::Bytes;
+use futures::{AsyncReadExt, AsyncSeekExt};
+use mime::Mime;
+use std::{collections::VecDeque, io::SeekFrom};
+
+/// A file sent as the response body, read piece by piece as it is written.
+///
+/// The file is served with an `ETag` and `Last-Modified` derived from its
+/// metadata, so the router answers conditional requests for it, and
+/// [`NamedFile::respond`] sends the ranges a request asks for.
+#[derive(Debug)]
+pub struct NamedFile {
+    file: File,
+    length: u64,
+    last_modified: Option<HttpDate>,
+    content_type: Mime,
+}
+
+impl NamedFile {
+    /// Opens a regular file, its content type is `application/octet-stream`
+    /// unless set with [`NamedFile::content_type`].
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, RhttpError> {
+        let file = File::open(path.as_ref()).await?;
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not a regular file", path.as_ref().display()),
+            )
+            .into());
+        }
+        let last_modified = metadata.modified().ok().map(HttpDate::from);
+        Ok(Self {
+            file,
+            length: metadata.len(),
+            last_modified,
+            content_type: mime::APPLICATION_OCTET_STREAM,
+        })
+    }
+
+    pub fn content_type(mut self, content_type: Mime) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// A strong tag of the modification time and length.
+    pub fn etag(&self) -> EntityTag {
+        let modified = self
+            .last_modified
+            .map_or(0, |modified| modified.timestamp());
+        EntityTag::strong(&format!("{modified:x}-{:x}", self.length))
+            .expect("hex digits are valid in an entity tag")
+    }
+
+    /// Sends the whole file, or the ranges `range` asks for when its
+    /// `If-Range` still matches the file.
+    pub fn respond<'rs>(self, range: &RangeRequest) -> Response<'rs> {
+        let etag = self.etag();
+        let current = Validators {
+            etag: Some(etag.clone()),
+            last_modified: self.last_modified,
+        };
+        let mut response = Response::default()
+            .with_header(ContentType(self.content_type))
+            .with_header(AcceptRanges::Bytes)
+            .with_header(ETag(etag));
+        if let Some(last_modified) = self.last_modified {
+            response = response.with_header(LastModified(last_modified));
+        }
+        let pieces = match range.resolve(self.length, &current) {
+            Ranges::Full => {
+                response = response.with_header(ContentLength(self.length));
+                vec![Piece::Segment(0..self.length)]
+            }
+            Ranges::Partial(ranges) => partial(&mut response, &ranges, self.length),
+            Ranges::Unsatisfiable => return unsatisfiable(self.length),
+        };
+        response.body = read_pieces(self.file, pieces);
+        response
+    }
+}
+
+impl IntoResponse for NamedFile {
+    fn into_response<'rs>(self) -> Response<'rs> {
+        self.respond(&RangeRequest::default())
+    }
+}
+
+/// Streams the pieces, segments are read from `file`.
+fn read_pieces(file: File, pieces: Vec<Piece>) -> Body {
+    Body::from_stream(futures::stream::try_unfold(
+        (file, VecDeque::from(pieces), 0),
+        |(mut file, mut pieces, mut remaining)| async move {
+            loop {
+                if remaining > 0 {
+                    let mut piece = vec![0; remaining.min(STREAM_CHUNK_LENGTH as u64) as usize];
+                    let read = file.read(&mut piece).await?;
+                    if read == 0 {
+                        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                    }
+                    piece.truncate(read);
+                    remaining -= read as u64;
+                    return Ok(Some((Bytes::from(piece), (file, pieces, remaining))));
+                }
+                match pieces.pop_front() {
+                    None => return Ok(None),
+                    Some(Piece::Literal(bytes)) => {
+                        return Ok(Some((bytes, (file, pieces, remaining))))
+                    }
+                    Some(Piece::Segment(segment)) => {
+                        file.seek(SeekFrom::Start(segment.start)).await?;
+                        remaining = segment.end - segment.start;
+                    }
+                }
+            }
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::{HeaderMap, HeaderType, HeaderValue};
+    use crate::method::Method;
+
+    async fn named_file(name: &str, content: &[u8]) -> NamedFile {
+        let path = std::env::temp_dir().join(format!("rhttp-{name}-{}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let file = NamedFile::open(&path).await.unwrap();
+        std::fs::remove_file(path).unwrap();
+        file
+    }
+
+    fn range(range: &str) -> RangeRequest {
+        let mut headers = HeaderMap::new();
+        headers.append(HeaderType::Range, HeaderValue::try_from(range).unwrap());
+        RangeRequest::from_request(Method::Get, &headers)
+    }
+
+    #[async_std::test]
+    async fn test_serve_file() {
+        let file = named_file("full", b"hello world").await;
+        assert_eq!(file.length(), 11);
+        let response = file.content_type(mime::TEXT_PLAIN).into_response();
+        assert_eq!(response.status_line.status_code, 200);
+        assert!(response.headers.contains_key(&HeaderType::ETag));
+        assert!(response.headers.contains_key(&HeaderType::LastModified));
+        assert_eq!(response.body.collect().await.unwrap(), "hello world");
+    }
+
+    #[async_std::test]
+    async fn test_serve_file_ranges() {
+        let file = named_file("range", b"hello world").await;
+        let response = file.respond(&range("bytes=-5"));
+        assert_eq!(response.status_line.status_code, 206);
+        assert_eq!(response.body.collect().await.unwrap(), "world");
+
+        let file = named_file("ranges", b"hello world").await;
+        let response = file.respond(&range("bytes=0-0,6-6"));
+        let length = response.headers.typed_get::<ContentLength>().unwrap();
+        let body = response.body.collect().await.unwrap();
+        assert_eq!(length, Some(ContentLength(body.len() as u64)));
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("Content-Range: bytes 0-0/11\r\n\r\nh\r\n"));
+        assert!(body.contains("Content-Range: bytes 6-6/11\r\n\r\nw\r\n"));
+
+        let file = named_file("unsatisfiable", b"hello world").await;
+        let response = file.respond(&range("bytes=11-"));
+        assert_eq!(response.status_line.status_code, 416);
+    }
+}
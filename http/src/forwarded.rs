@@ -0,0 +1,424 @@
+use crate::{
+    common::RhttpError::{self, InvalidHeader},
+    headers::{Header, HeaderMap, HeaderType, HeaderValue, Host},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+/// A block of addresses in CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`.
+/// A bare address is a block of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    /// Bits of `addr` beyond the prefix are cleared.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, RhttpError> {
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > bits {
+            return Err(InvalidHeader(format!("CIDR prefix /{prefix} of {addr}")));
+        }
+        let addr = match addr {
+            IpAddr::V4(ip) => IpAddr::from(Ipv4Addr::from(u32::from(ip) & mask(prefix, 32) as u32)),
+            IpAddr::V6(ip) => IpAddr::from(Ipv6Addr::from(u128::from(ip) & mask(prefix, 128))),
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// IPv4 addresses mapped into IPv6 belong to the IPv4 blocks.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                u32::from(ip) & mask(self.prefix, 32) as u32 == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                u128::from(ip) & mask(self.prefix, 128) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The upper `prefix` of `bits` bits set.
+fn mask(prefix: u8, bits: u32) -> u128 {
+    match prefix {
+        0 => 0,
+        prefix => (u128::MAX << (128 - u32::from(prefix))) >> (128 - bits),
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = RhttpError;
+
+    fn from_str(cidr: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidHeader(format!("CIDR {cidr:?}"));
+        let (addr, prefix) = match cidr.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (cidr, None),
+        };
+        let addr = addr.parse::<IpAddr>().map_err(|_| invalid())?;
+        let prefix = match prefix {
+            Some(prefix) if prefix.bytes().all(|b| b.is_ascii_digit()) => {
+                prefix.parse().map_err(|_| invalid())?
+            }
+            Some(_) => return Err(invalid()),
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(addr, prefix)
+    }
+}
+
+impl std::fmt::Display for IpNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Proxies whose forwarding headers are believed, anyone else could have
+/// written them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts every address of `cidr`, e.g. `10.0.0.0/8`.
+    pub fn trust(mut self, cidr: &str) -> Result<Self, RhttpError> {
+        self.0.push(cidr.parse()?);
+        Ok(self)
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(ip))
+    }
+}
+
+/// Where a request originally came from, as far as trusted proxies tell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIp {
+    /// The client, or the peer when it is not a trusted proxy.
+    pub ip: IpAddr,
+    /// `http` unless a trusted proxy says the client used another scheme.
+    pub scheme: String,
+    /// The host the client asked for, the `Host` of the request unless a
+    /// trusted proxy forwarded another one.
+    pub host: Option<Host>,
+}
+
+/// A hop of the forwarding chain, `None` for an unknown or obfuscated node.
+#[derive(Debug, Default)]
+struct Hop {
+    ip: Option<IpAddr>,
+    scheme: Option<String>,
+    host: Option<Host>,
+}
+
+impl ClientIp {
+    /// Walks the hops `Forwarded` (RFC 7239) lists, or `X-Forwarded-For` and
+    /// then `X-Real-IP` without it, from the peer backwards for as long as
+    /// they are trusted proxies. The first untrusted hop is the client.
+    ///
+    /// Scheme and host come from the `Forwarded` element of the client, or
+    /// the last `X-Forwarded-Proto` and `X-Forwarded-Host` values, which the
+    /// nearest proxy added.
+    pub fn resolve(peer: IpAddr, headers: &HeaderMap, trusted: &TrustedProxies) -> Self {
+        let mut client = Self {
+            ip: peer.to_canonical(),
+            scheme: String::from("http"),
+            host: headers.typed_get::<Host>().ok().flatten(),
+        };
+        if !trusted.contains(&peer) {
+            return client;
+        }
+        let hops = match forwarded_hops(headers) {
+            Some(hops) => hops,
+            None => {
+                let hops = x_forwarded_hops(headers);
+                if let Some(scheme) =
+                    last_element(headers, "X-Forwarded-Proto").filter(|s| is_scheme(s))
+                {
+                    client.scheme = scheme.to_ascii_lowercase();
+                }
+                if let Some(host) = last_element(headers, "X-Forwarded-Host").and_then(parse_host) {
+                    client.host = Some(host);
+                }
+                hops
+            }
+        };
+        for hop in hops.into_iter().rev() {
+            let Some(ip) = hop.ip else {
+                break;
+            };
+            client.ip = ip.to_canonical();
+            if let Some(scheme) = hop.scheme {
+                client.scheme = scheme;
+            }
+            if let Some(host) = hop.host {
+                client.host = Some(host);
+            }
+            if !trusted.contains(&ip) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+fn custom(name: &str) -> HeaderType {
+    HeaderType::Custom(name.to_owned())
+}
+
+fn elements<'h>(headers: &'h HeaderMap, name: &str) -> impl Iterator<Item = &'h str> {
+    headers
+        .get_all(&custom(name))
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+}
+
+fn last_element<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+    elements(headers, name).last()
+}
+
+/// The elements of `Forwarded`, `None` without the header.
+fn forwarded_hops(headers: &HeaderMap) -> Option<Vec<Hop>> {
+    if !headers.contains_key(&HeaderType::Forwarded) {
+        return None;
+    }
+    let joined = headers
+        .get_all(&HeaderType::Forwarded)
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    let hops = split_quoted(&joined, ',')
+        .into_iter()
+        .filter(|element| !element.trim().is_empty())
+        .map(|element| {
+            let mut hop = Hop::default();
+            for pair in split_quoted(element, ';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = unquote(value.trim());
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "for" => hop.ip = parse_node(&value),
+                    "proto" if is_scheme(&value) => hop.scheme = Some(value.to_ascii_lowercase()),
+                    "host" => hop.host = parse_host(&value),
+                    _ => {}
+                }
+            }
+            hop
+        })
+        .collect();
+    Some(hops)
+}
+
+/// `X-Forwarded-For`, or `X-Real-IP` without it.
+fn x_forwarded_hops(headers: &HeaderMap) -> Vec<Hop> {
+    let mut nodes = elements(headers, "X-Forwarded-For").peekable();
+    let nodes: Vec<&str> = match nodes.peek() {
+        Some(_) => nodes.collect(),
+        None => last_element(headers, "X-Real-IP").into_iter().collect(),
+    };
+    nodes
+        .into_iter()
+        .map(|node| Hop {
+            ip: parse_node(node),
+            ..Hop::default()
+        })
+        .collect()
+}
+
+/// An address with an optional port, IPv6 ones in brackets when they have a
+/// port. `unknown` and obfuscated identifiers such as `_hidden` are `None`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+        .or_else(|| {
+            let ip = node.strip_prefix('[')?.strip_suffix(']')?;
+            ip.parse::<IpAddr>().ok()
+        })
+}
+
+fn parse_host(host: &str) -> Option<Host> {
+    let value = HeaderValue::try_from(host).ok()?;
+    Host::decode(std::iter::once(&value)).ok()
+}
+
+/// `scheme`, RFC 3986 section 3.1.
+fn is_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b))
+}
+
+/// Splits at `separator` outside of quoted strings.
+fn split_quoted(value: &str, separator: char) -> Vec<&str> {
+    let (mut parts, mut start, mut quoted, mut escaped) = (Vec::new(), 0, false, false);
+    for (at, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&value[start..at]);
+                start = at + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(quoted) => {
+            let mut unquoted = String::with_capacity(quoted.len());
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                unquoted.push(match c {
+                    '\\' => chars.next().unwrap_or('\\'),
+                    c => c,
+                });
+            }
+            unquoted
+        }
+        None => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(headers: &[(&str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(
+                name.parse().unwrap(),
+                HeaderValue::try_from(*value).unwrap(),
+            );
+        }
+        map
+    }
+
+    fn trusted() -> TrustedProxies {
+        TrustedProxies::new()
+            .trust("10.0.0.0/8")
+            .unwrap()
+            .trust("fd00::/8")
+            .unwrap()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ip_net() {
+        let net = "10.1.2.3/8".parse::<IpNet>().unwrap();
+        assert_eq!(net.to_string(), "10.0.0.0/8");
+        assert!(net.contains(&ip("10.255.0.1")));
+        assert!(net.contains(&ip("::ffff:10.0.0.1")));
+        assert!(!net.contains(&ip("11.0.0.1")));
+        assert!("0.0.0.0/0"
+            .parse::<IpNet>()
+            .unwrap()
+            .contains(&ip("8.8.8.8")));
+        assert!("fd00::/8"
+            .parse::<IpNet>()
+            .unwrap()
+            .contains(&ip("fd12::1")));
+        assert!(!"::1".parse::<IpNet>().unwrap().contains(&ip("::2")));
+        for cidr in ["10.0.0.0/33", "10.0.0.0/+8", "10.0.0/8", "::/129", ""] {
+            assert!(cidr.parse::<IpNet>().is_err(), "{cidr}");
+        }
+    }
+
+    #[test]
+    fn test_untrusted_peer_is_the_client() {
+        let headers = headers(&[
+            ("Host", "example.test"),
+            ("X-Forwarded-For", "203.0.113.9"),
+            ("X-Forwarded-Proto", "https"),
+        ]);
+        let client = ClientIp::resolve(ip("198.51.100.1"), &headers, &trusted());
+        assert_eq!(client.ip, ip("198.51.100.1"));
+        assert_eq!(client.scheme, "http");
+        assert_eq!(client.host.unwrap().hostname(), "example.test");
+    }
+
+    #[test]
+    fn test_x_forwarded_for() {
+        let headers = headers(&[
+            ("X-Forwarded-For", "192.0.2.1, 203.0.113.9"),
+            ("X-Forwarded-For", "10.0.0.2"),
+            ("X-Forwarded-Proto", "https"),
+            ("X-Forwarded-Host", "public.test:8443"),
+        ]);
+        let client = ClientIp::resolve(ip("10.0.0.1"), &headers, &trusted());
+        // 192.0.2.1 was sent by the untrusted client and is ignored
+        assert_eq!(client.ip, ip("203.0.113.9"));
+        assert_eq!(client.scheme, "https");
+        let host = client.host.unwrap();
+        assert_eq!((host.hostname(), host.port()), ("public.test", Some(8443)));
+
+        let real_ip = ClientIp::resolve(
+            ip("::ffff:10.0.0.1"),
+            &self::headers(&[("X-Real-IP", "203.0.113.7")]),
+            &trusted(),
+        );
+        assert_eq!(real_ip.ip, ip("203.0.113.7"));
+
+        // a chain of trusted proxies only, the first one is the client
+        let all_trusted = self::headers(&[("X-Forwarded-For", "10.0.0.3, 10.0.0.2")]);
+        let client = ClientIp::resolve(ip("10.0.0.1"), &all_trusted, &trusted());
+        assert_eq!(client.ip, ip("10.0.0.3"));
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let headers = headers(&[
+            (
+                "Forwarded",
+                r#"for=192.0.2.60;proto=http, for="[2001:db8:cafe::17]:4711";proto=https;host="public.test""#,
+            ),
+            ("Forwarded", "for=fd00::1"),
+            ("X-Forwarded-For", "198.51.100.2"),
+        ]);
+        let client = ClientIp::resolve(ip("10.0.0.1"), &headers, &trusted());
+        assert_eq!(client.ip, ip("2001:db8:cafe::17"));
+        assert_eq!(client.scheme, "https");
+        assert_eq!(client.host.unwrap().hostname(), "public.test");
+
+        // an obfuscated node ends the walk at the last known proxy
+        let obfuscated = self::headers(&[("Forwarded", "for=_hidden, for=10.0.0.2")]);
+        let client = ClientIp::resolve(ip("10.0.0.1"), &obfuscated, &trusted());
+        assert_eq!(client.ip, ip("10.0.0.2"));
+    }
+}
@@ -0,0 +1,637 @@
+use crate::{
+    body::Body,
+    common::{
+        RhttpError::{self, ConnectionClosed, ContentTooLarge, InvalidFraming, ParsingResponseErr},
+        CRLF, FINAL_CRLF,
+    },
+    headers::{HeaderMap, HeaderType, HeaderValue},
+    response::StatusLine,
+    status_code::Status,
+    version::ProtocolVersion,
+};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, Stream, StreamExt,
+};
+
+/// How forgiving parsers and serializers are towards messages that bend RFC 9112.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Rejects bare LF line endings, obs-fold, whitespace before the colon,
+    /// conflicting `Content-Length`/`Transfer-Encoding` and bytes left over
+    /// after the message. Messages that pass can't be read differently by
+    /// another HTTP implementation, which is what request smuggling relies on.
+    #[default]
+    Strict,
+    /// Accepts the above the way RFC 9112 allows recipients to recover from
+    /// them: bare LF ends a line, obs-fold is unfolded, `Transfer-Encoding`
+    /// overrides `Content-Length` and trailing bytes are ignored.
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    pub(crate) strictness: Strictness,
+    pub(crate) head_request: bool,
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// The response answers a `HEAD` request, so it has no body whatever
+    /// its `Content-Length` says.
+    pub fn head_request(mut self, head_request: bool) -> Self {
+        self.head_request = head_request;
+        self
+    }
+}
+
+/// How the length of a message body is determined, RFC 9112 section 6.3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    Empty,
+    ContentLength(usize),
+    Chunked,
+    CloseDelimited,
+}
+
+#[derive(Debug)]
+pub struct ResponseHead {
+    pub status_line: StatusLine<'static>,
+    pub headers: HeaderMap,
+    pub framing: BodyFraming,
+}
+
+/// Upper bound for the start line and header section of a message.
+const MAX_HEAD_LENGTH: usize = 64 * 1024;
+
+/// Largest piece a streamed body is read in.
+pub(crate) const STREAM_CHUNK_LENGTH: usize = 16 * 1024;
+
+/// Reads the start line and header section including the terminating empty line.
+pub async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Vec<u8>, RhttpError> {
+    let mut head = Vec::new();
+    loop {
+        let line_start = head.len();
+        if reader.read_until(b'\n', &mut head).await? == 0 {
+            return Err(if head.is_empty() {
+                ConnectionClosed
+            } else {
+                InvalidFraming(String::from("connection closed inside of the head"))
+            });
+        }
+        if head.len() > MAX_HEAD_LENGTH {
+            return Err(InvalidFraming(String::from("head too large")));
+        }
+        if line_start > 0 && matches!(&head[line_start..], b"\n" | b"\r\n") {
+            return Ok(head);
+        }
+    }
+}
+
+/// Reads and parses a response head, see [`read_body`] and [`stream_body`]
+/// for reading the body that follows.
+pub async fn read_response_head<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    options: &ParseOptions,
+) -> Result<ResponseHead, RhttpError> {
+    let head = read_head(reader).await?;
+    parse_response_head(&head, options)
+}
+
+/// Framing of a request body, a request is never delimited by closing the connection.
+pub fn request_framing(headers: &HeaderMap) -> Result<BodyFraming, RhttpError> {
+    let content_length = content_length(
+        headers
+            .get_all(&HeaderType::ContentLength)
+            .map(HeaderValue::as_bytes),
+    )?;
+    let transfer_encoding = headers.get_joined(&HeaderType::TransferEncoding);
+    match (transfer_encoding, content_length) {
+        (Some(_), Some(_)) => Err(InvalidFraming(String::from(
+            "both Transfer-Encoding and Content-Length are present",
+        ))),
+        (Some(coding), None) => match last_transfer_coding(coding.as_bytes()) {
+            Some(coding) if coding.eq_ignore_ascii_case("chunked") => Ok(BodyFraming::Chunked),
+            _ => Err(InvalidFraming(format!(
+                "request Transfer-Encoding {coding:?} does not end with chunked"
+            ))),
+        },
+        (None, Some(0) | None) => Ok(BodyFraming::Empty),
+        (None, Some(length)) => Ok(BodyFraming::ContentLength(length)),
+    }
+}
+
+/// Reads the whole body into memory, a body longer than `max_length` fails
+/// with `ContentTooLarge` before more than `max_length` bytes are buffered.
+pub async fn read_body<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    framing: BodyFraming,
+    strictness: Strictness,
+    max_length: u64,
+) -> Result<Bytes, RhttpError> {
+    let mut body = Vec::new();
+    match framing {
+        BodyFraming::Empty => {}
+        BodyFraming::ContentLength(length) => {
+            if length as u64 > max_length {
+                return Err(ContentTooLarge(max_length));
+            }
+            read_exact_into(reader, &mut body, length).await?;
+        }
+        BodyFraming::Chunked => loop {
+            let size = read_chunk_size(reader, strictness).await?;
+            if size == 0 {
+                break;
+            }
+            if (body.len() as u64).saturating_add(size as u64) > max_length {
+                return Err(ContentTooLarge(max_length));
+            }
+            read_exact_into(reader, &mut body, size).await?;
+            read_chunk_end(reader, strictness).await?;
+        },
+        BodyFraming::CloseDelimited => {
+            reader
+                .take(max_length.saturating_add(1))
+                .read_to_end(&mut body)
+                .await?;
+            if body.len() as u64 > max_length {
+                return Err(ContentTooLarge(max_length));
+            }
+        }
+    }
+    Ok(Bytes::from(body))
+}
+
+/// Appends exactly `length` bytes to `body`, growing it piece by piece so a
+/// peer announcing more than it sends does not get the memory up front.
+async fn read_exact_into<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    body: &mut Vec<u8>,
+    length: usize,
+) -> Result<(), RhttpError> {
+    let mut remaining = length;
+    while remaining > 0 {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Err(InvalidFraming(String::from(
+                "connection closed inside of the body",
+            )));
+        }
+        let read = available.len().min(remaining).min(STREAM_CHUNK_LENGTH);
+        body.extend_from_slice(&available[..read]);
+        reader.consume_unpin(read);
+        remaining -= read;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum StreamState {
+    ContentLength(usize),
+    UntilClose,
+    ChunkSize,
+    ChunkData(usize),
+}
+
+/// Turns the body that follows on `reader` into a [`Body::Stream`] yielding
+/// it as it arrives, chunked framing is removed.
+pub fn stream_body<R>(reader: R, framing: BodyFraming, strictness: Strictness) -> Body
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    match framing {
+        BodyFraming::Empty => Body::empty(),
+        framing => Body::from_stream(body_pieces(reader, framing, strictness)),
+    }
+}
+
+/// The body that follows on `reader` in the pieces it arrives in, chunked
+/// framing is removed. Unlike [`stream_body`] `reader` may be borrowed.
+pub fn body_pieces<R>(
+    reader: R,
+    framing: BodyFraming,
+    strictness: Strictness,
+) -> impl Stream<Item = Result<Bytes, RhttpError>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let state = match framing {
+        BodyFraming::Empty => StreamState::ContentLength(0),
+        BodyFraming::ContentLength(length) => StreamState::ContentLength(length),
+        BodyFraming::Chunked => StreamState::ChunkSize,
+        BodyFraming::CloseDelimited => StreamState::UntilClose,
+    };
+    futures::stream::try_unfold((reader, state), move |(mut reader, mut state)| async move {
+        loop {
+            let (remaining, next): (usize, fn(usize) -> StreamState) = match state {
+                StreamState::ContentLength(0) => return Ok(None),
+                StreamState::ContentLength(remaining) => (remaining, StreamState::ContentLength),
+                StreamState::UntilClose => (usize::MAX, |_| StreamState::UntilClose),
+                StreamState::ChunkSize => {
+                    match read_chunk_size(&mut reader, strictness).await? {
+                        0 => return Ok(None),
+                        size => state = StreamState::ChunkData(size),
+                    }
+                    continue;
+                }
+                StreamState::ChunkData(0) => {
+                    read_chunk_end(&mut reader, strictness).await?;
+                    state = StreamState::ChunkSize;
+                    continue;
+                }
+                StreamState::ChunkData(remaining) => (remaining, StreamState::ChunkData),
+            };
+
+            let mut piece = vec![0; remaining.min(STREAM_CHUNK_LENGTH)];
+            let read = reader.read(&mut piece).await?;
+            if read == 0 {
+                return match state {
+                    StreamState::UntilClose => Ok(None),
+                    _ => Err(InvalidFraming(String::from(
+                        "connection closed inside of the body",
+                    ))),
+                };
+            }
+            piece.truncate(read);
+            let state = next(remaining.saturating_sub(read));
+            return Ok(Some((Bytes::from(piece), (reader, state))));
+        }
+    })
+}
+
+/// Writes the pieces of a streamed body, chunk encoded when `chunked`,
+/// empty pieces are skipped as they would end a chunked body.
+pub async fn write_pieces<W, S>(
+    writer: &mut W,
+    mut pieces: S,
+    chunked: bool,
+) -> Result<(), RhttpError>
+where
+    W: AsyncWrite + Unpin,
+    S: Stream<Item = Result<Bytes, RhttpError>> + Unpin,
+{
+    while let Some(piece) = pieces.next().await {
+        let piece = piece?;
+        if piece.is_empty() {
+            continue;
+        }
+        if chunked {
+            writer
+                .write_all(format!("{:x}{CRLF}", piece.len()).as_bytes())
+                .await?;
+            writer.write_all(&piece).await?;
+            writer.write_all(CRLF.as_bytes()).await?;
+        } else {
+            writer.write_all(&piece).await?;
+        }
+    }
+    if chunked {
+        writer
+            .write_all(format!("0{FINAL_CRLF}").as_bytes())
+            .await?;
+    }
+    Ok(writer.flush().await?)
+}
+
+/// Reads a chunk size line, on the last chunk the trailer section is consumed too.
+async fn read_chunk_size<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    strictness: Strictness,
+) -> Result<usize, RhttpError> {
+    let size = parse_chunk_size(strip_line_ending(&read_line(reader).await?, strictness)?)?;
+    if size == 0 {
+        // skip the trailer section, bounded like a head
+        let mut trailers = 0;
+        loop {
+            let line = read_line(reader).await?;
+            trailers += line.len();
+            if trailers > MAX_HEAD_LENGTH {
+                return Err(InvalidFraming(String::from("trailer section too large")));
+            }
+            if strip_line_ending(&line, strictness)?.is_empty() {
+                break;
+            }
+        }
+    }
+    Ok(size)
+}
+
+/// Reads the line ending that follows chunk data.
+async fn read_chunk_end<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    strictness: Strictness,
+) -> Result<(), RhttpError> {
+    if !strip_line_ending(&read_line(reader).await?, strictness)?.is_empty() {
+        return Err(InvalidFraming(String::from(
+            "chunk data longer than its size",
+        )));
+    }
+    Ok(())
+}
+
+/// Reads a single line including its line ending, at most [`MAX_HEAD_LENGTH`] bytes.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<String, RhttpError> {
+    let mut line = Vec::new();
+    let mut limited = reader.take(MAX_HEAD_LENGTH as u64);
+    if limited.read_until(b'\n', &mut line).await? == 0 {
+        return Err(InvalidFraming(String::from(
+            "connection closed inside of chunked body",
+        )));
+    }
+    if !line.ends_with(b"\n") && line.len() >= MAX_HEAD_LENGTH {
+        return Err(InvalidFraming(String::from("chunked body line too long")));
+    }
+    Ok(String::from_utf8(line)?)
+}
+
+/// Length of the head including the empty line that terminates it, `None`
+/// if the head is not complete yet.
+pub(crate) fn head_length(bytes: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while let Some(idx) = bytes[start..].iter().position(|b| *b == b'\n') {
+        let line = &bytes[start..start + idx];
+        start += idx + 1;
+        if line.is_empty() || line == b"\r" {
+            return Some(start);
+        }
+    }
+    None
+}
+
+/// Strips the line ending, bare LF is only accepted when lenient.
+pub(crate) fn strip_line_ending(line: &str, strictness: Strictness) -> Result<&str, RhttpError> {
+    let stripped = strip_line_ending_bytes(line.as_bytes(), strictness)?;
+    Ok(&line[..stripped.len()])
+}
+
+fn strip_line_ending_bytes(line: &[u8], strictness: Strictness) -> Result<&[u8], RhttpError> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    match line.strip_suffix(b"\r") {
+        Some(line) => Ok(line),
+        None if strictness == Strictness::Lenient => Ok(line),
+        None => Err(ParsingResponseErr(String::from(
+            "line not terminated by CRLF",
+        ))),
+    }
+}
+
+/// Parses the status line and header section, `head` has to include the
+/// empty line terminating it. Field values are kept as bytes, they may carry
+/// obs-text.
+pub(crate) fn parse_response_head(
+    head: &[u8],
+    options: &ParseOptions,
+) -> Result<ResponseHead, RhttpError> {
+    let strictness = options.strictness;
+    let mut lines = head.split_inclusive(|b| *b == b'\n');
+    let status_line = strip_line_ending_bytes(lines.next().unwrap_or_default(), strictness)?;
+    let status_line = parse_status_line(str::from_utf8(status_line)?, strictness)?;
+
+    let mut fields: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut terminated = false;
+    for line in lines {
+        let line = strip_line_ending_bytes(line, strictness)?;
+        if line.is_empty() {
+            terminated = true;
+            break;
+        }
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            // obs-fold, a continuation of the previous field value
+            match (strictness, fields.last_mut()) {
+                (Strictness::Lenient, Some((_, value))) => {
+                    value.push(b' ');
+                    value.extend_from_slice(line.trim_ascii());
+                    continue;
+                }
+                _ => {
+                    return Err(ParsingResponseErr(format!(
+                        "obsolete line folding {:?}",
+                        line.escape_ascii().to_string()
+                    )))
+                }
+            }
+        }
+        let colon = line.iter().position(|b| *b == b':').ok_or_else(|| {
+            ParsingResponseErr(format!(
+                "malformed header {:?}",
+                line.escape_ascii().to_string()
+            ))
+        })?;
+        let name = str::from_utf8(&line[..colon])?;
+        let value = &line[colon + 1..];
+        let name = match strictness {
+            Strictness::Strict => name,
+            Strictness::Lenient => name.trim_end(),
+        };
+        if name.is_empty() || name.ends_with([' ', '\t']) {
+            return Err(ParsingResponseErr(format!(
+                "malformed header name {name:?}"
+            )));
+        }
+        fields.push((name.to_owned(), value.trim_ascii().to_vec()));
+    }
+    if !terminated {
+        return Err(ParsingResponseErr(String::from(
+            "incomplete header section",
+        )));
+    }
+
+    let framing = response_framing(status_line.status_code, &mut fields, options)?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in fields {
+        headers.append(
+            HeaderType::from_string(name)?,
+            HeaderValue::from_bytes(&value)?,
+        );
+    }
+    Ok(ResponseHead {
+        status_line,
+        headers,
+        framing,
+    })
+}
+
+fn parse_status_line(
+    line: &str,
+    strictness: Strictness,
+) -> Result<StatusLine<'static>, RhttpError> {
+    let malformed = || ParsingResponseErr(format!("malformed status line {line:?}"));
+    let mut parts = line.splitn(3, ' ');
+    let version = parts
+        .next()
+        .unwrap_or_default()
+        .parse::<ProtocolVersion>()?;
+    let status_code = parts
+        .next()
+        .filter(|code| code.len() == 3 && code.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|code| code.parse::<usize>().ok())
+        .filter(|code| *code >= 100)
+        .ok_or_else(malformed)?;
+    // the space before an empty reason phrase is still required
+    if strictness == Strictness::Strict && parts.next().is_none() {
+        return Err(malformed());
+    }
+    // the reason phrase is informational only, the registered one is kept instead
+    let reason_phrase = Status::from_status_code(status_code).map_or("", |status| status.as_str());
+    Ok(StatusLine {
+        version,
+        status_code,
+        reason_phrase,
+    })
+}
+
+fn response_framing(
+    status_code: usize,
+    fields: &mut Vec<(String, Vec<u8>)>,
+    options: &ParseOptions,
+) -> Result<BodyFraming, RhttpError> {
+    let is_field = |name: &str, field: &str| name.eq_ignore_ascii_case(field);
+    let content_length = content_length(
+        fields
+            .iter()
+            .filter(|(name, _)| is_field(name, "Content-Length"))
+            .map(|(_, value)| value.as_slice()),
+    )?;
+    let transfer_coding = fields
+        .iter()
+        .filter(|(name, _)| is_field(name, "Transfer-Encoding"))
+        .filter_map(|(_, value)| last_transfer_coding(value))
+        .next_back()
+        .map(|coding| coding.eq_ignore_ascii_case("chunked"));
+
+    if options.head_request || (100..200).contains(&status_code) || matches!(status_code, 204 | 304)
+    {
+        return Ok(BodyFraming::Empty);
+    }
+    match (transfer_coding, content_length) {
+        (Some(_), Some(_)) if options.strictness == Strictness::Strict => Err(InvalidFraming(
+            String::from("both Transfer-Encoding and Content-Length are present"),
+        )),
+        (Some(chunked), _) => {
+            // Transfer-Encoding overrides Content-Length, which must not be forwarded
+            fields.retain(|(name, _)| !is_field(name, "Content-Length"));
+            if chunked {
+                Ok(BodyFraming::Chunked)
+            } else {
+                Ok(BodyFraming::CloseDelimited)
+            }
+        }
+        (None, Some(length)) => Ok(BodyFraming::ContentLength(length)),
+        (None, None) => Ok(BodyFraming::CloseDelimited),
+    }
+}
+
+/// Final transfer coding of a `Transfer-Encoding` value.
+pub(crate) fn last_transfer_coding(value: &[u8]) -> Option<&str> {
+    str::from_utf8(value)
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .rfind(|coding| !coding.is_empty())
+}
+
+/// Validates every `Content-Length` value (repeated fields and lists included)
+/// agree on a single length.
+pub(crate) fn content_length<'v>(
+    values: impl Iterator<Item = &'v [u8]>,
+) -> Result<Option<usize>, RhttpError> {
+    let mut length = None;
+    let values = values
+        .map(|value| {
+            str::from_utf8(value).map_err(|_| {
+                InvalidFraming(format!(
+                    "invalid Content-Length {:?}",
+                    value.escape_ascii().to_string()
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for value in values.into_iter().flat_map(|value| value.split(',')) {
+        let value = value.trim();
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(InvalidFraming(format!("invalid Content-Length {value:?}")));
+        }
+        let parsed = value
+            .parse::<usize>()
+            .map_err(|_| InvalidFraming(format!("invalid Content-Length {value:?}")))?;
+        if length.is_some_and(|length| length != parsed) {
+            return Err(InvalidFraming(String::from(
+                "conflicting Content-Length values",
+            )));
+        }
+        length = Some(parsed);
+    }
+    Ok(length)
+}
+
+/// Parses the hexadecimal size of a chunk, dropping any chunk extensions.
+pub(crate) fn parse_chunk_size(line: &str) -> Result<usize, RhttpError> {
+    let size = line.split(';').next().unwrap_or_default().trim_end();
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(InvalidFraming(format!("invalid chunk size {size:?}")));
+    }
+    usize::from_str_radix(size, 16)
+        .map_err(|_| InvalidFraming(format!("chunk size {size:?} too large")))
+}
+
+/// Decodes a chunked body from the beginning of `bytes`, returns the body
+/// and the number of bytes consumed including the trailer section.
+pub(crate) fn decode_chunked(
+    bytes: &[u8],
+    strictness: Strictness,
+) -> Result<(Bytes, usize), RhttpError> {
+    let incomplete = || ParsingResponseErr(String::from("incomplete chunked body"));
+    let mut body = BytesMut::new();
+    let mut position = 0;
+    loop {
+        let line = next_line(&bytes[position..]).ok_or_else(incomplete)?;
+        position += line.len();
+        let size = parse_chunk_size(strip_line_ending(std::str::from_utf8(line)?, strictness)?)?;
+        if size == 0 {
+            // skip the trailer section
+            loop {
+                let line = next_line(&bytes[position..]).ok_or_else(incomplete)?;
+                position += line.len();
+                if strip_line_ending(std::str::from_utf8(line)?, strictness)?.is_empty() {
+                    return Ok((body.freeze(), position));
+                }
+            }
+        }
+        let end = position
+            .checked_add(size)
+            .ok_or_else(|| InvalidFraming(format!("chunk size {size:x} too large")))?;
+        let data = bytes.get(position..end).ok_or_else(incomplete)?;
+        body.put(data);
+        position += size;
+        let line = next_line(&bytes[position..]).ok_or_else(incomplete)?;
+        position += line.len();
+        if !strip_line_ending(std::str::from_utf8(line)?, strictness)?.is_empty() {
+            return Err(InvalidFraming(String::from(
+                "chunk data longer than its size",
+            )));
+        }
+    }
+}
+
+fn next_line(bytes: &[u8]) -> Option<&[u8]> {
+    let idx = bytes.iter().position(|b| *b == b'\n')?;
+    Some(&bytes[..=idx])
+}
+
+/// Encodes the whole body as a single chunk followed by the last chunk.
+pub(crate) fn encode_chunked(body: &[u8], buff: &mut BytesMut) {
+    if !body.is_empty() {
+        buff.put(format!("{:x}{CRLF}", body.len()).as_bytes());
+        buff.put(body);
+        buff.put(CRLF.as_bytes());
+    }
+    buff.put(format!("0{FINAL_CRLF}").as_bytes());
+}
@@ -1,10 +1,63 @@
 use core::str;
-use std::{fmt::Formatter, str::FromStr};
+use std::{
+    cmp::Ordering,
+    fmt::Formatter,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
-use crate::common::RhttpError;
+use crate::common::RhttpError::{self, InvalidHeaderName, InvalidHeaderValue};
+use bytes::{Bytes, BytesMut};
 
-#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
-pub enum HeaderType {
+mod typed;
+
+pub use typed::{
+    Accept, AcceptEncoding, AcceptRanges, Authorization, ByteRange, CacheControl, Challenge,
+    ContentCoding, ContentEncoding, ContentLength, ContentRange, ContentType, Date, ETag,
+    EntityTag, EntityTagList, Expires, Header, Host, IfMatch, IfModifiedSince, IfNoneMatch,
+    IfRange, IfUnmodifiedSince, LastModified, Location, MediaRange, Quality, Range, RetryAfter,
+    Vary, WwwAuthenticate,
+};
+
+macro_rules! standard_headers {
+    ($($(#[$docs:meta])* ($variant:ident, $name:literal, $lowercase:literal, $constant:ident);)+) => {
+        /// Name of a header field.
+        ///
+        /// The permanent names of the IANA HTTP Field Name Registry have their
+        /// own variant, anything else is `Custom`. Names are compared, hashed
+        /// and ordered ignoring ASCII case (RFC 9110 section 5.1), a `Custom`
+        /// name keeps its original spelling for output.
+        #[derive(Clone)]
+        pub enum HeaderType {
+            $($(#[$docs])* $variant,)+
+            Custom(String),
+        }
+
+        $(
+            #[doc = concat!("The `", $name, "` header.")]
+            pub const $constant: HeaderType = HeaderType::$variant;
+        )+
+
+        impl HeaderType {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(HeaderType::$variant => $name,)+
+                    HeaderType::Custom(name) => name,
+                }
+            }
+
+            fn from_lowercase(name: &[u8]) -> Option<Self> {
+                match name {
+                    $($lowercase => Some(HeaderType::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+standard_headers! {
+    (AIm, "A-IM", b"a-im", A_IM);
     /// The HTTP Accept request header indicates which content types, expressed
     /// as MIME types, the client is able to understand.
     ///
@@ -13,7 +66,11 @@ pub enum HeaderType {
     /// Browsers set required values for this header based on the context of the
     /// request. For example, a browser uses different values in a request when
     /// fetching a CSS stylesheet, image, video, or a script.
-    Accept,
+    (Accept, "Accept", b"accept", ACCEPT);
+    (AcceptAdditions, "Accept-Additions", b"accept-additions", ACCEPT_ADDITIONS);
+    (AcceptCh, "Accept-CH", b"accept-ch", ACCEPT_CH);
+    (AcceptCharset, "Accept-Charset", b"accept-charset", ACCEPT_CHARSET);
+    (AcceptDatetime, "Accept-Datetime", b"accept-datetime", ACCEPT_DATETIME);
     /// The HTTP Accept-Encoding request header indicates the content encoding
     /// (usually a compression algorithm) that the client can understand.
     ///
@@ -36,26 +93,61 @@ pub enum HeaderType {
     /// As long as the identity;q=0 or *;q=0 directives do not explicitly forbid
     /// the identity value that means no encoding, the server must never return
     /// a 406 Not Acceptable error.
-    AcceptEncoding,
-    /// The Host request header specifies the host and port number of the server
-    /// to which the request is being sent.
-    ///
-    /// If no port is included, the default port for the service requested is
-    /// implied (e.g., 443 for an HTTPS URL, and 80 for an HTTP URL).
-    /// A Host header field must be sent in all HTTP/1.1 request messages.
-    ///
-    /// A 400 (Bad Request) status code may be sent to any HTTP/1.1 request
-    /// message that lacks or contains more than one Host header field.
-    /// Host, The HTTP Connection header controls whether the network connection
+    (AcceptEncoding, "Accept-Encoding", b"accept-encoding", ACCEPT_ENCODING);
+    (AcceptFeatures, "Accept-Features", b"accept-features", ACCEPT_FEATURES);
+    (AcceptLanguage, "Accept-Language", b"accept-language", ACCEPT_LANGUAGE);
+    (AcceptPatch, "Accept-Patch", b"accept-patch", ACCEPT_PATCH);
+    (AcceptPost, "Accept-Post", b"accept-post", ACCEPT_POST);
+    (AcceptRanges, "Accept-Ranges", b"accept-ranges", ACCEPT_RANGES);
+    (AcceptSignature, "Accept-Signature", b"accept-signature", ACCEPT_SIGNATURE);
+    (AccessControlAllowCredentials, "Access-Control-Allow-Credentials", b"access-control-allow-credentials", ACCESS_CONTROL_ALLOW_CREDENTIALS);
+    (AccessControlAllowHeaders, "Access-Control-Allow-Headers", b"access-control-allow-headers", ACCESS_CONTROL_ALLOW_HEADERS);
+    (AccessControlAllowMethods, "Access-Control-Allow-Methods", b"access-control-allow-methods", ACCESS_CONTROL_ALLOW_METHODS);
+    (AccessControlAllowOrigin, "Access-Control-Allow-Origin", b"access-control-allow-origin", ACCESS_CONTROL_ALLOW_ORIGIN);
+    (AccessControlExposeHeaders, "Access-Control-Expose-Headers", b"access-control-expose-headers", ACCESS_CONTROL_EXPOSE_HEADERS);
+    (AccessControlMaxAge, "Access-Control-Max-Age", b"access-control-max-age", ACCESS_CONTROL_MAX_AGE);
+    (AccessControlRequestHeaders, "Access-Control-Request-Headers", b"access-control-request-headers", ACCESS_CONTROL_REQUEST_HEADERS);
+    (AccessControlRequestMethod, "Access-Control-Request-Method", b"access-control-request-method", ACCESS_CONTROL_REQUEST_METHOD);
+    (Age, "Age", b"age", AGE);
+    (Allow, "Allow", b"allow", ALLOW);
+    (Alpn, "ALPN", b"alpn", ALPN);
+    (AltSvc, "Alt-Svc", b"alt-svc", ALT_SVC);
+    (AltUsed, "Alt-Used", b"alt-used", ALT_USED);
+    (Alternates, "Alternates", b"alternates", ALTERNATES);
+    (ApplyToRedirectRef, "Apply-To-Redirect-Ref", b"apply-to-redirect-ref", APPLY_TO_REDIRECT_REF);
+    (AuthenticationControl, "Authentication-Control", b"authentication-control", AUTHENTICATION_CONTROL);
+    (AuthenticationInfo, "Authentication-Info", b"authentication-info", AUTHENTICATION_INFO);
+    (Authorization, "Authorization", b"authorization", AUTHORIZATION);
+    (CacheControl, "Cache-Control", b"cache-control", CACHE_CONTROL);
+    (CacheStatus, "Cache-Status", b"cache-status", CACHE_STATUS);
+    (CalManagedId, "Cal-Managed-ID", b"cal-managed-id", CAL_MANAGED_ID);
+    (CalDAVTimezones, "CalDAV-Timezones", b"caldav-timezones", CALDAV_TIMEZONES);
+    (CapsuleProtocol, "Capsule-Protocol", b"capsule-protocol", CAPSULE_PROTOCOL);
+    (CdnCacheControl, "CDN-Cache-Control", b"cdn-cache-control", CDN_CACHE_CONTROL);
+    (CdnLoop, "CDN-Loop", b"cdn-loop", CDN_LOOP);
+    (CertNotAfter, "Cert-Not-After", b"cert-not-after", CERT_NOT_AFTER);
+    (CertNotBefore, "Cert-Not-Before", b"cert-not-before", CERT_NOT_BEFORE);
+    (ClearSiteData, "Clear-Site-Data", b"clear-site-data", CLEAR_SITE_DATA);
+    (ClientCert, "Client-Cert", b"client-cert", CLIENT_CERT);
+    (ClientCertChain, "Client-Cert-Chain", b"client-cert-chain", CLIENT_CERT_CHAIN);
+    /// The HTTP Connection header controls whether the network connection
     /// stays open after the current transaction finishes.
     ///
     /// If the value sent is keep-alive, the connection is persistent and not
     /// closed, allowing subsequent requests to the same server on the same
     /// connection.
-    Connection,
+    (Connection, "Connection", b"connection", CONNECTION);
+    (ContentDigest, "Content-Digest", b"content-digest", CONTENT_DIGEST);
+    (ContentDisposition, "Content-Disposition", b"content-disposition", CONTENT_DISPOSITION);
+    (ContentEncoding, "Content-Encoding", b"content-encoding", CONTENT_ENCODING);
+    (ContentLanguage, "Content-Language", b"content-language", CONTENT_LANGUAGE);
     /// The HTTP Content-Length header indicates the size, in bytes, of the
     /// message body sent to the recipient.
-    ContentLength,
+    (ContentLength, "Content-Length", b"content-length", CONTENT_LENGTH);
+    (ContentLocation, "Content-Location", b"content-location", CONTENT_LOCATION);
+    (ContentRange, "Content-Range", b"content-range", CONTENT_RANGE);
+    (ContentSecurityPolicy, "Content-Security-Policy", b"content-security-policy", CONTENT_SECURITY_POLICY);
+    (ContentSecurityPolicyReportOnly, "Content-Security-Policy-Report-Only", b"content-security-policy-report-only", CONTENT_SECURITY_POLICY_REPORT_ONLY);
     /// The HTTP Content-Type representation header is used to indicate the
     /// original media type of a resource before any content encoding is applied.
     ///
@@ -68,11 +160,30 @@ pub enum HeaderType {
     /// about content type handling, a 415 client error response may be returned.
     /// The Content-Type header differs from Content-Encoding in that Content-Encoding
     /// helps the recipient understand how to decode data to its original form.
-    ContentType,
-    /// The User-Agent request header is a characteristic string that lets
-    /// servers and network peers identify the application, operating system,
-    /// vendor, and/or version of the requesting user agent.
-    UserAgent,
+    (ContentType, "Content-Type", b"content-type", CONTENT_TYPE);
+    (Cookie, "Cookie", b"cookie", COOKIE);
+    (CrossOriginEmbedderPolicy, "Cross-Origin-Embedder-Policy", b"cross-origin-embedder-policy", CROSS_ORIGIN_EMBEDDER_POLICY);
+    (CrossOriginEmbedderPolicyReportOnly, "Cross-Origin-Embedder-Policy-Report-Only", b"cross-origin-embedder-policy-report-only", CROSS_ORIGIN_EMBEDDER_POLICY_REPORT_ONLY);
+    (CrossOriginOpenerPolicy, "Cross-Origin-Opener-Policy", b"cross-origin-opener-policy", CROSS_ORIGIN_OPENER_POLICY);
+    (CrossOriginOpenerPolicyReportOnly, "Cross-Origin-Opener-Policy-Report-Only", b"cross-origin-opener-policy-report-only", CROSS_ORIGIN_OPENER_POLICY_REPORT_ONLY);
+    (CrossOriginResourcePolicy, "Cross-Origin-Resource-Policy", b"cross-origin-resource-policy", CROSS_ORIGIN_RESOURCE_POLICY);
+    (Dasl, "DASL", b"dasl", DASL);
+    /// The HTTP Date request and response header contains the date and
+    /// time at which the message originated.
+    (Date, "Date", b"date", DATE);
+    (Dav, "DAV", b"dav", DAV);
+    (DeltaBase, "Delta-Base", b"delta-base", DELTA_BASE);
+    (Depth, "Depth", b"depth", DEPTH);
+    (Destination, "Destination", b"destination", DESTINATION);
+    (DPoP, "DPoP", b"dpop", DPOP);
+    (DPoPNonce, "DPoP-Nonce", b"dpop-nonce", DPOP_NONCE);
+    (EarlyData, "Early-Data", b"early-data", EARLY_DATA);
+    (ETag, "ETag", b"etag", ETAG);
+    (Expect, "Expect", b"expect", EXPECT);
+    (Expires, "Expires", b"expires", EXPIRES);
+    (Forwarded, "Forwarded", b"forwarded", FORWARDED);
+    (From, "From", b"from", FROM);
+    (Hobareg, "Hobareg", b"hobareg", HOBAREG);
     /// The Host request header specifies the host and port number of the server
     /// to which the request is being sent.
     ///
@@ -82,89 +193,282 @@ pub enum HeaderType {
     /// A Host header field must be sent in all HTTP/1.1 request messages.
     /// A 400 (Bad Request) status code may be sent to any HTTP/1.1 request
     /// message that lacks or contains more than one Host header field.
-    Host,
-    /// The HTTP Date request and response header contains the date and
-    /// time at which the message originated.
-    Date,
-    Custom(String),
+    (Host, "Host", b"host", HOST);
+    (If, "If", b"if", IF);
+    (IfMatch, "If-Match", b"if-match", IF_MATCH);
+    (IfModifiedSince, "If-Modified-Since", b"if-modified-since", IF_MODIFIED_SINCE);
+    (IfNoneMatch, "If-None-Match", b"if-none-match", IF_NONE_MATCH);
+    (IfRange, "If-Range", b"if-range", IF_RANGE);
+    (IfScheduleTagMatch, "If-Schedule-Tag-Match", b"if-schedule-tag-match", IF_SCHEDULE_TAG_MATCH);
+    (IfUnmodifiedSince, "If-Unmodified-Since", b"if-unmodified-since", IF_UNMODIFIED_SINCE);
+    (Im, "IM", b"im", IM);
+    (IncludeReferredTokenBindingId, "Include-Referred-Token-Binding-ID", b"include-referred-token-binding-id", INCLUDE_REFERRED_TOKEN_BINDING_ID);
+    (KeepAlive, "Keep-Alive", b"keep-alive", KEEP_ALIVE);
+    (Label, "Label", b"label", LABEL);
+    (LastEventId, "Last-Event-ID", b"last-event-id", LAST_EVENT_ID);
+    (LastModified, "Last-Modified", b"last-modified", LAST_MODIFIED);
+    (Link, "Link", b"link", LINK);
+    (Location, "Location", b"location", LOCATION);
+    (LockToken, "Lock-Token", b"lock-token", LOCK_TOKEN);
+    (MaxForwards, "Max-Forwards", b"max-forwards", MAX_FORWARDS);
+    (MementoDatetime, "Memento-Datetime", b"memento-datetime", MEMENTO_DATETIME);
+    (Meter, "Meter", b"meter", METER);
+    (MimeVersion, "MIME-Version", b"mime-version", MIME_VERSION);
+    (Negotiate, "Negotiate", b"negotiate", NEGOTIATE);
+    (Nel, "NEL", b"nel", NEL);
+    (ODataEntityId, "OData-EntityId", b"odata-entityid", ODATA_ENTITYID);
+    (ODataIsolation, "OData-Isolation", b"odata-isolation", ODATA_ISOLATION);
+    (ODataMaxVersion, "OData-MaxVersion", b"odata-maxversion", ODATA_MAXVERSION);
+    (ODataVersion, "OData-Version", b"odata-version", ODATA_VERSION);
+    (OptionalWwwAuthenticate, "Optional-WWW-Authenticate", b"optional-www-authenticate", OPTIONAL_WWW_AUTHENTICATE);
+    (OrderingType, "Ordering-Type", b"ordering-type", ORDERING_TYPE);
+    (Origin, "Origin", b"origin", ORIGIN);
+    (OriginAgentCluster, "Origin-Agent-Cluster", b"origin-agent-cluster", ORIGIN_AGENT_CLUSTER);
+    (Oscore, "OSCORE", b"oscore", OSCORE);
+    (OslcCoreVersion, "OSLC-Core-Version", b"oslc-core-version", OSLC_CORE_VERSION);
+    (Overwrite, "Overwrite", b"overwrite", OVERWRITE);
+    (PingFrom, "Ping-From", b"ping-from", PING_FROM);
+    (PingTo, "Ping-To", b"ping-to", PING_TO);
+    (Position, "Position", b"position", POSITION);
+    (Prefer, "Prefer", b"prefer", PREFER);
+    (PreferenceApplied, "Preference-Applied", b"preference-applied", PREFERENCE_APPLIED);
+    (Priority, "Priority", b"priority", PRIORITY);
+    (ProxyAuthenticate, "Proxy-Authenticate", b"proxy-authenticate", PROXY_AUTHENTICATE);
+    (ProxyAuthenticationInfo, "Proxy-Authentication-Info", b"proxy-authentication-info", PROXY_AUTHENTICATION_INFO);
+    (ProxyAuthorization, "Proxy-Authorization", b"proxy-authorization", PROXY_AUTHORIZATION);
+    (ProxyStatus, "Proxy-Status", b"proxy-status", PROXY_STATUS);
+    (PublicKeyPins, "Public-Key-Pins", b"public-key-pins", PUBLIC_KEY_PINS);
+    (PublicKeyPinsReportOnly, "Public-Key-Pins-Report-Only", b"public-key-pins-report-only", PUBLIC_KEY_PINS_REPORT_ONLY);
+    (Range, "Range", b"range", RANGE);
+    (RedirectRef, "Redirect-Ref", b"redirect-ref", REDIRECT_REF);
+    (Referer, "Referer", b"referer", REFERER);
+    (Refresh, "Refresh", b"refresh", REFRESH);
+    (ReplayNonce, "Replay-Nonce", b"replay-nonce", REPLAY_NONCE);
+    (ReprDigest, "Repr-Digest", b"repr-digest", REPR_DIGEST);
+    (RetryAfter, "Retry-After", b"retry-after", RETRY_AFTER);
+    (ScheduleReply, "Schedule-Reply", b"schedule-reply", SCHEDULE_REPLY);
+    (ScheduleTag, "Schedule-Tag", b"schedule-tag", SCHEDULE_TAG);
+    (SecPurpose, "Sec-Purpose", b"sec-purpose", SEC_PURPOSE);
+    (SecTokenBinding, "Sec-Token-Binding", b"sec-token-binding", SEC_TOKEN_BINDING);
+    (SecWebSocketAccept, "Sec-WebSocket-Accept", b"sec-websocket-accept", SEC_WEBSOCKET_ACCEPT);
+    (SecWebSocketExtensions, "Sec-WebSocket-Extensions", b"sec-websocket-extensions", SEC_WEBSOCKET_EXTENSIONS);
+    (SecWebSocketKey, "Sec-WebSocket-Key", b"sec-websocket-key", SEC_WEBSOCKET_KEY);
+    (SecWebSocketProtocol, "Sec-WebSocket-Protocol", b"sec-websocket-protocol", SEC_WEBSOCKET_PROTOCOL);
+    (SecWebSocketVersion, "Sec-WebSocket-Version", b"sec-websocket-version", SEC_WEBSOCKET_VERSION);
+    (Server, "Server", b"server", SERVER);
+    (ServerTiming, "Server-Timing", b"server-timing", SERVER_TIMING);
+    (SetCookie, "Set-Cookie", b"set-cookie", SET_COOKIE);
+    (Signature, "Signature", b"signature", SIGNATURE);
+    (SignatureInput, "Signature-Input", b"signature-input", SIGNATURE_INPUT);
+    (Slug, "SLUG", b"slug", SLUG);
+    (SoapAction, "SoapAction", b"soapaction", SOAPACTION);
+    (StatusUri, "Status-URI", b"status-uri", STATUS_URI);
+    (StrictTransportSecurity, "Strict-Transport-Security", b"strict-transport-security", STRICT_TRANSPORT_SECURITY);
+    (Sunset, "Sunset", b"sunset", SUNSET);
+    (SurrogateCapability, "Surrogate-Capability", b"surrogate-capability", SURROGATE_CAPABILITY);
+    (SurrogateControl, "Surrogate-Control", b"surrogate-control", SURROGATE_CONTROL);
+    (Tcn, "TCN", b"tcn", TCN);
+    (Te, "TE", b"te", TE);
+    (Timeout, "Timeout", b"timeout", TIMEOUT);
+    (Topic, "Topic", b"topic", TOPIC);
+    (Traceparent, "Traceparent", b"traceparent", TRACEPARENT);
+    (Tracestate, "Tracestate", b"tracestate", TRACESTATE);
+    (Trailer, "Trailer", b"trailer", TRAILER);
+    (TransferEncoding, "Transfer-Encoding", b"transfer-encoding", TRANSFER_ENCODING);
+    (Ttl, "TTL", b"ttl", TTL);
+    (Upgrade, "Upgrade", b"upgrade", UPGRADE);
+    (Urgency, "Urgency", b"urgency", URGENCY);
+    /// The User-Agent request header is a characteristic string that lets
+    /// servers and network peers identify the application, operating system,
+    /// vendor, and/or version of the requesting user agent.
+    (UserAgent, "User-Agent", b"user-agent", USER_AGENT);
+    (VariantVary, "Variant-Vary", b"variant-vary", VARIANT_VARY);
+    (Vary, "Vary", b"vary", VARY);
+    (Via, "Via", b"via", VIA);
+    (WantContentDigest, "Want-Content-Digest", b"want-content-digest", WANT_CONTENT_DIGEST);
+    (WantReprDigest, "Want-Repr-Digest", b"want-repr-digest", WANT_REPR_DIGEST);
+    (WwwAuthenticate, "WWW-Authenticate", b"www-authenticate", WWW_AUTHENTICATE);
+    (XContentTypeOptions, "X-Content-Type-Options", b"x-content-type-options", X_CONTENT_TYPE_OPTIONS);
+    (XFrameOptions, "X-Frame-Options", b"x-frame-options", X_FRAME_OPTIONS);
 }
 
+/// Longest name of the registry, no longer name needs to be looked up.
+const MAX_STANDARD_LEN: usize = 40;
+
 impl std::fmt::Debug for HeaderType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let str_version = match self {
-            HeaderType::Accept => "Accept",
-            HeaderType::Connection => "Connection",
-            HeaderType::ContentLength => "Content-Length",
-            HeaderType::ContentType => "Content-Type",
-            HeaderType::AcceptEncoding => "Accept-Encoding",
-            HeaderType::UserAgent => "User-Agent",
-            HeaderType::Host => "Host",
-            HeaderType::Date => "Date",
-            HeaderType::Custom(x) => x,
-        };
-        write!(f, "{}", str_version)
+        f.write_str(self.as_str())
     }
 }
 
 impl std::fmt::Display for HeaderType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let str_version = match self {
-            HeaderType::Accept => "Accept",
-            HeaderType::Connection => "Connection",
-            HeaderType::ContentLength => "Content-Length",
-            HeaderType::ContentType => "Content-Type",
-            HeaderType::AcceptEncoding => "Accept-Encoding",
-            HeaderType::UserAgent => "User-Agent",
-            HeaderType::Host => "Host",
-            HeaderType::Date => "Date",
-            HeaderType::Custom(x) => x,
-        };
-        write!(f, "{}", str_version)
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for HeaderType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (HeaderType::Custom(_), _) | (_, HeaderType::Custom(_)) => {
+                self.as_str().eq_ignore_ascii_case(other.as_str())
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for HeaderType {}
+
+impl Hash for HeaderType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for byte in self.as_str().bytes() {
+            state.write_u8(byte.to_ascii_lowercase());
+        }
+        state.write_u8(0xff);
+    }
+}
+
+impl PartialOrd for HeaderType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeaderType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let other = other.as_str().bytes().map(|byte| byte.to_ascii_lowercase());
+        self.as_str()
+            .bytes()
+            .map(|byte| byte.to_ascii_lowercase())
+            .cmp(other)
     }
 }
 
 impl HeaderType {
+    /// Header names are tokens, RFC 9110 section 5.1.
     pub fn from_string(s: String) -> Result<Self, RhttpError> {
-        if let Ok(std_header) = HeaderType::try_into_std(&s) {
-            Ok(std_header)
-        } else {
-            // TODO: add validation of Custom header
-            // this is always true but i just created bare bone structure for future validation
-            // if let result = Ok(Self::Custom(s)) {
-            // return result;
+        if let Some(standard) = HeaderType::standard(s.as_bytes()) {
+            Ok(standard)
+        } else if is_token(&s) {
             Ok(Self::Custom(s))
-            // }
-            // Err(RhttpError::ParsingHttpHeaderErr)
+        } else {
+            Err(InvalidHeaderName(s))
+        }
+    }
+
+    /// Parses a name as read from the wire, a registered name allocates nothing.
+    pub fn from_bytes(name: &[u8]) -> Result<Self, RhttpError> {
+        if let Some(standard) = HeaderType::standard(name) {
+            return Ok(standard);
+        }
+        match str::from_utf8(name) {
+            Ok(name) if is_token(name) => Ok(Self::Custom(name.to_owned())),
+            _ => Err(InvalidHeaderName(name.escape_ascii().to_string())),
         }
     }
 
-    fn try_into_std(s: &str) -> Result<Self, RhttpError> {
-        match s {
-            "Accept" => Ok(Self::Accept),
-            "Connection" => Ok(Self::Connection),
-            "Content-Length" => Ok(Self::ContentLength),
-            "Content-Type" => Ok(Self::ContentType),
-            "Accept-Encoding" => Ok(Self::AcceptEncoding),
-            "User-Agent" => Ok(Self::UserAgent),
-            "Host" => Ok(Self::Host),
-            "Date" => Ok(Self::Date),
-            _ => Err(RhttpError::ParsingHttpHeaderErr),
+    fn standard(name: &[u8]) -> Option<Self> {
+        if name.len() > MAX_STANDARD_LEN {
+            return None;
         }
+        let mut lowercase = [0; MAX_STANDARD_LEN];
+        let lowercase = &mut lowercase[..name.len()];
+        for (lower, byte) in lowercase.iter_mut().zip(name) {
+            *lower = byte.to_ascii_lowercase();
+        }
+        HeaderType::from_lowercase(lowercase)
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone)]
-pub struct HeaderValue(Box<str>);
+impl FromStr for HeaderType {
+    type Err = RhttpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(s.as_bytes())
+    }
+}
+
+/// Value of a header field as the bytes sent on the wire.
+///
+/// Values may carry obs-text, bytes beyond ASCII such as Latin-1 file names,
+/// so [`HeaderValue::to_str`] is only a view for the UTF-8 ones. A sensitive
+/// value, credentials for instance, is left out of `Debug` output.
+#[derive(Clone)]
+pub struct HeaderValue {
+    bytes: Bytes,
+    sensitive: bool,
+}
 
 impl HeaderValue {
-    pub fn to_str(&self) -> &str {
-        self.as_ref()
+    /// Values are field-content, RFC 9110 section 5.5, so no CR, LF or other
+    /// control character can split the message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RhttpError> {
+        if !is_field_value(bytes) {
+            return Err(InvalidHeaderValue(bytes.escape_ascii().to_string()));
+        }
+        Ok(Self::from_encoded(Bytes::copy_from_slice(bytes)))
+    }
+
+    /// Values built by this crate, already known to be field-content.
+    pub(crate) fn from_encoded(bytes: impl Into<Bytes>) -> Self {
+        Self {
+            bytes: bytes.into(),
+            sensitive: false,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The value as text, fails when it is not valid UTF-8.
+    pub fn to_str(&self) -> Result<&str, RhttpError> {
+        Ok(str::from_utf8(&self.bytes)?)
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    pub fn set_sensitive(&mut self, sensitive: bool) {
+        self.sensitive = sensitive;
     }
 }
 
 impl std::fmt::Debug for HeaderValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.as_ref())
+        match self.sensitive {
+            true => f.write_str("Sensitive"),
+            false => write!(f, "{}", self.bytes.escape_ascii()),
+        }
+    }
+}
+
+/// Sensitivity is not part of the value, equal bytes are equal values.
+impl PartialEq for HeaderValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for HeaderValue {}
+
+impl Hash for HeaderValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
+
+impl PartialEq<str> for HeaderValue {
+    fn eq(&self, other: &str) -> bool {
+        self.bytes == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for HeaderValue {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
     }
 }
 
@@ -172,7 +476,7 @@ impl FromStr for HeaderValue {
     type Err = RhttpError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(Box::from(s)))
+        Self::from_bytes(s.as_bytes())
     }
 }
 
@@ -180,7 +484,7 @@ impl TryFrom<usize> for HeaderValue {
     type Error = RhttpError;
 
     fn try_from(value: usize) -> Result<Self, Self::Error> {
-        Ok(Self(Box::from(format!("{}", value))))
+        Ok(Self::from_encoded(value.to_string()))
     }
 }
 
@@ -200,8 +504,445 @@ impl TryFrom<String> for HeaderValue {
     }
 }
 
-impl AsRef<str> for HeaderValue {
-    fn as_ref(&self) -> &str {
-        self.0.as_ref()
+impl TryFrom<&[u8]> for HeaderValue {
+    type Error = RhttpError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(value)
+    }
+}
+
+impl AsRef<[u8]> for HeaderValue {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+pub(crate) fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Headers carrying credentials, kept out of `Debug` output.
+const SENSITIVE_HEADERS: [HeaderType; 4] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+fn is_sensitive(key: &HeaderType) -> bool {
+    SENSITIVE_HEADERS.contains(key)
+}
+
+/// Visible characters, spaces and tabs, and bytes beyond ASCII (obs-text).
+pub(crate) fn is_field_value(value: &[u8]) -> bool {
+    value
+        .iter()
+        .all(|b| *b == b'\t' || (b' '..=b'~').contains(b) || *b >= 0x80)
+}
+
+/// Header fields of a message, kept in the order they were added.
+///
+/// A name can hold several values, [`HeaderMap::append`] adds one while
+/// [`HeaderMap::insert`] replaces all of them. Names are matched ignoring
+/// case like [`HeaderType`] comparisons are.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(HeaderType, HeaderValue)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of fields, each value of a repeated name counts once.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Makes `value` the only value of `key`. It takes the place of the first
+    /// value it replaces, which is returned.
+    pub fn insert(&mut self, key: HeaderType, mut value: HeaderValue) -> Option<HeaderValue> {
+        value.sensitive |= is_sensitive(&key);
+        let Some(position) = self.position(&key) else {
+            self.entries.push((key, value));
+            return None;
+        };
+        let previous = std::mem::replace(&mut self.entries[position], (key, value)).1;
+        let key = self.entries[position].0.clone();
+        let mut idx = 0;
+        self.entries.retain(|(header_type, _)| {
+            idx += 1;
+            idx <= position + 1 || *header_type != key
+        });
+        Some(previous)
+    }
+
+    /// Adds a value after the existing ones.
+    pub fn append(&mut self, key: HeaderType, mut value: HeaderValue) {
+        value.sensitive |= is_sensitive(&key);
+        self.entries.push((key, value));
+    }
+
+    /// Sets `value` unless `key` already has one.
+    pub fn insert_if_absent(&mut self, key: HeaderType, value: HeaderValue) {
+        if !self.contains_key(&key) {
+            self.append(key, value);
+        }
+    }
+
+    /// First value of `key`.
+    pub fn get(&self, key: &HeaderType) -> Option<&HeaderValue> {
+        self.position(key).map(|position| &self.entries[position].1)
+    }
+
+    pub fn get_all(&self, key: &HeaderType) -> impl Iterator<Item = &HeaderValue> {
+        let key = key.clone();
+        self.entries
+            .iter()
+            .filter(move |(header_type, _)| *header_type == key)
+            .map(|(_, header_value)| header_value)
+    }
+
+    /// All values of `key` joined with `", "`, the combined field value of
+    /// RFC 9110 section 5.3. Not meaningful for `Set-Cookie`, whose values
+    /// may contain commas.
+    pub fn get_joined(&self, key: &HeaderType) -> Option<HeaderValue> {
+        let mut values = self.get_all(key);
+        let mut joined = values.next()?.clone();
+        for value in values {
+            let mut bytes = BytesMut::from(joined.as_bytes());
+            bytes.extend_from_slice(b", ");
+            bytes.extend_from_slice(value.as_bytes());
+            joined.bytes = bytes.freeze();
+            joined.sensitive |= value.sensitive;
+        }
+        Some(joined)
+    }
+
+    /// Checks every name is a token and every value field-content, as
+    /// `Custom` names and typed headers are not checked when they are built.
+    pub fn validate(&self) -> Result<(), RhttpError> {
+        for (header_type, header_value) in self.iter() {
+            if !is_token(header_type.as_str()) {
+                return Err(InvalidHeaderName(header_type.to_string()));
+            }
+            if !is_field_value(header_value.as_bytes()) {
+                return Err(InvalidHeaderValue(
+                    header_value.as_bytes().escape_ascii().to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the typed header, `None` when the message doesn't have it.
+    pub fn typed_get<H: Header>(&self) -> Result<Option<H>, RhttpError> {
+        let name = H::name();
+        match self.contains_key(&name) {
+            true => H::decode(self.get_all(&name)).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Replaces every value of the header with the encoded one, fails when
+    /// the encoding is not a valid field value.
+    pub fn typed_insert<H: Header>(&mut self, header: H) -> Result<(), RhttpError> {
+        self.insert(H::name(), header.encode()?);
+        Ok(())
+    }
+
+    pub fn contains_key(&self, key: &HeaderType) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Removes every value of `key` and returns the first one.
+    pub fn remove(&mut self, key: &HeaderType) -> Option<HeaderValue> {
+        let first = self
+            .position(key)
+            .map(|position| self.entries.remove(position).1);
+        self.entries.retain(|(header_type, _)| header_type != key);
+        first
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&HeaderType, &HeaderValue) -> bool) {
+        self.entries
+            .retain(|(header_type, header_value)| keep(header_type, header_value));
+    }
+
+    /// Fields in insertion order, a repeated name is yielded once per value.
+    pub fn iter(&self) -> impl Iterator<Item = (&HeaderType, &HeaderValue)> {
+        self.entries
+            .iter()
+            .map(|(header_type, header_value)| (header_type, header_value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &HeaderType> {
+        self.entries.iter().map(|(header_type, _)| header_type)
+    }
+
+    fn position(&self, key: &HeaderType) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(header_type, _)| header_type == key)
+    }
+}
+
+impl std::fmt::Debug for HeaderMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl IntoIterator for HeaderMap {
+    type Item = (HeaderType, HeaderValue);
+    type IntoIter = std::vec::IntoIter<(HeaderType, HeaderValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl FromIterator<(HeaderType, HeaderValue)> for HeaderMap {
+    fn from_iter<T: IntoIterator<Item = (HeaderType, HeaderValue)>>(iter: T) -> Self {
+        let mut headers = Self::new();
+        headers.extend(iter);
+        headers
+    }
+}
+
+impl Extend<(HeaderType, HeaderValue)> for HeaderMap {
+    fn extend<T: IntoIterator<Item = (HeaderType, HeaderValue)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.append(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashSet};
+
+    fn text(value: &HeaderValue) -> &str {
+        value.to_str().unwrap()
+    }
+
+    #[test]
+    fn test_standard_names_ignore_case() {
+        for name in ["content-length", "CONTENT-LENGTH", "Content-Length"] {
+            let header_type = HeaderType::from_string(name.to_owned()).unwrap();
+            assert!(matches!(header_type, HeaderType::ContentLength));
+            assert_eq!(header_type.to_string(), "Content-Length");
+        }
+        assert_eq!(HeaderType::Custom(String::from("host")), HeaderType::Host);
+    }
+
+    #[test]
+    fn test_registered_names_parse_from_bytes() {
+        let cases: [(&[u8], HeaderType); 5] = [
+            (b"transfer-encoding", TRANSFER_ENCODING),
+            (b"WWW-Authenticate", WWW_AUTHENTICATE),
+            (b"sec-websocket-key", HeaderType::SecWebSocketKey),
+            (b"TE", TE),
+            (
+                b"Cross-Origin-Embedder-Policy-Report-Only",
+                CROSS_ORIGIN_EMBEDDER_POLICY_REPORT_ONLY,
+            ),
+        ];
+        for (name, expected) in cases {
+            let parsed = HeaderType::from_bytes(name).unwrap();
+            assert!(std::mem::discriminant(&parsed) == std::mem::discriminant(&expected));
+        }
+        assert_eq!(COOKIE.as_str(), "Cookie");
+        assert_eq!("etag".parse::<HeaderType>().unwrap().to_string(), "ETag");
+
+        let custom = HeaderType::from_bytes(b"X-Request-Id").unwrap();
+        assert!(matches!(&custom, HeaderType::Custom(name) if name == "X-Request-Id"));
+        assert!(HeaderType::from_bytes(&[b'x'; MAX_STANDARD_LEN + 1]).is_ok());
+        assert!(matches!(
+            HeaderType::from_bytes(b"Bad\xff"),
+            Err(InvalidHeaderName(_))
+        ));
+        assert_eq!(HeaderType::Custom(String::from("origin")), ORIGIN);
+    }
+
+    #[test]
+    fn test_custom_names_ignore_case_and_keep_spelling() {
+        let lower = HeaderType::from_string(String::from("x-request-id")).unwrap();
+        let mixed = HeaderType::from_string(String::from("X-Request-ID")).unwrap();
+        assert_eq!(lower, mixed);
+        assert_eq!(lower.cmp(&mixed), Ordering::Equal);
+        assert_eq!(mixed.to_string(), "X-Request-ID");
+
+        let set: HashSet<HeaderType> = [lower.clone(), mixed.clone()].into_iter().collect();
+        assert_eq!(set.len(), 1);
+
+        let mut headers = BTreeMap::new();
+        headers.insert(mixed, HeaderValue::try_from("1").unwrap());
+        assert_eq!(headers.get(&lower).map(text), Some("1"));
+        assert_eq!(headers.keys().next().unwrap().to_string(), "X-Request-ID");
+    }
+
+    #[test]
+    fn test_ordering_ignores_case() {
+        let mut names = [
+            HeaderType::Custom(String::from("x-b")),
+            HeaderType::Custom(String::from("X-A")),
+            HeaderType::ContentType,
+        ];
+        names.sort();
+        let names: Vec<String> = names.iter().map(HeaderType::to_string).collect();
+        assert_eq!(names, ["Content-Type", "X-A", "x-b"]);
+    }
+
+    fn value(value: &str) -> HeaderValue {
+        HeaderValue::try_from(value).unwrap()
+    }
+
+    #[test]
+    fn test_header_map_append_and_insert() {
+        let set_cookie = HeaderType::Custom(String::from("Set-Cookie"));
+        let mut headers = HeaderMap::new();
+        headers.append(set_cookie.clone(), value("a=1"));
+        headers.append(HeaderType::ContentType, value("text/plain"));
+        headers.append(HeaderType::Custom(String::from("set-cookie")), value("b=2"));
+
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers.get(&set_cookie).map(text), Some("a=1"));
+        let cookies: Vec<&str> = headers.get_all(&set_cookie).map(text).collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert_eq!(
+            headers.get_joined(&set_cookie).as_ref().map(text),
+            Some("a=1, b=2")
+        );
+
+        let previous = headers.insert(set_cookie.clone(), value("c=3"));
+        assert_eq!(previous.as_ref().map(text), Some("a=1"));
+        let fields: Vec<(String, &str)> = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), text(value)))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (String::from("Set-Cookie"), "c=3"),
+                (String::from("Content-Type"), "text/plain")
+            ]
+        );
+
+        headers.insert_if_absent(HeaderType::ContentType, value("text/html"));
+        assert_eq!(
+            headers.get(&HeaderType::ContentType).map(text),
+            Some("text/plain")
+        );
+        assert_eq!(
+            headers.insert(HeaderType::Host, value("example.test")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_header_map_remove_keeps_order() {
+        let vary = HeaderType::Custom(String::from("Vary"));
+        let mut headers: HeaderMap = [
+            (HeaderType::Date, value("now")),
+            (vary.clone(), value("Accept")),
+            (HeaderType::Host, value("example.test")),
+            (vary.clone(), value("Accept-Encoding")),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(headers.remove(&vary).as_ref().map(text), Some("Accept"));
+        assert!(!headers.contains_key(&vary));
+        let names: Vec<String> = headers.keys().map(HeaderType::to_string).collect();
+        assert_eq!(names, ["Date", "Host"]);
+        assert_eq!(headers.get_joined(&vary), None);
+    }
+
+    #[test]
+    fn test_names_and_values_are_validated() {
+        assert!(HeaderType::from_string(String::from("X-Ok_1")).is_ok());
+        for name in ["", "X Space", "X:Colon", "X-Crlf\r\nInjected", "Ünicode"] {
+            assert!(matches!(
+                HeaderType::from_string(name.to_owned()),
+                Err(InvalidHeaderName(_))
+            ));
+        }
+
+        assert!(HeaderValue::try_from("a\tb ~ é").is_ok());
+        for value in ["a\r\nSet-Cookie: b=1", "a\nb", "a\rb", "nul\0", "del\x7f"] {
+            assert!(matches!(
+                HeaderValue::try_from(value),
+                Err(InvalidHeaderValue(_))
+            ));
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.append(HeaderType::Custom(String::from("X-Ok")), value("1"));
+        assert!(headers.validate().is_ok());
+        headers.append(HeaderType::Custom(String::from("X\r\nBad")), value("1"));
+        assert!(matches!(headers.validate(), Err(InvalidHeaderName(_))));
+    }
+
+    #[test]
+    fn test_values_are_bytes() {
+        let latin1 = HeaderValue::from_bytes(b"caf\xe9").unwrap();
+        assert_eq!(latin1.as_bytes(), b"caf\xe9");
+        assert!(matches!(
+            latin1.to_str(),
+            Err(RhttpError::HeaderValueErr(_))
+        ));
+        assert_eq!(format!("{latin1:?}"), "caf\\xe9");
+        assert_eq!(value("café").to_str().unwrap(), "café");
+        assert!(HeaderValue::from_bytes(b"a\r\nb").is_err());
+    }
+
+    #[test]
+    fn test_sensitive_values_are_hidden() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            HeaderType::Custom(String::from("authorization")),
+            value("Bearer secret"),
+        );
+        headers.insert(
+            HeaderType::Custom(String::from("Cookie")),
+            value("id=secret"),
+        );
+        headers.append(
+            HeaderType::Custom(String::from("X-Trace")),
+            value("visible"),
+        );
+
+        let debug = format!("{headers:?}");
+        assert!(!debug.contains("secret"));
+        assert!(debug.contains("visible"));
+        assert!(
+            headers
+                .iter()
+                .filter(|(_, value)| value.is_sensitive())
+                .count()
+                == 2
+        );
+
+        let mut token = value("token");
+        token.set_sensitive(true);
+        assert_eq!(format!("{token:?}"), "Sensitive");
+        assert_eq!(token, value("token"));
+    }
+
+    #[test]
+    fn test_collected_sensitive_values_are_hidden() {
+        let mut headers: HeaderMap = [(HeaderType::Authorization, value("Bearer secret"))]
+            .into_iter()
+            .collect();
+        headers.extend([(HeaderType::Cookie, value("id=secret"))]);
+
+        assert!(headers.iter().all(|(_, value)| value.is_sensitive()));
+        assert!(!format!("{headers:?}").contains("secret"));
     }
 }
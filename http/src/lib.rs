@@ -6,3 +6,13 @@ pub mod version;
 pub mod common;
 pub mod response;
 pub mod status_code;
+pub mod client;
+pub mod framing;
+pub mod body;
+pub mod cookie;
+pub mod date;
+pub mod conditional;
+pub mod range;
+pub mod file;
+pub mod compression;
+pub mod forwarded;
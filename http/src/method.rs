@@ -42,6 +42,11 @@ impl Method {
         }
     }
 
+    /// Sending the request again has the same effect as sending it once, RFC 9110 section 9.2.2.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self, Self::Get | Self::Head | Self::Options | Self::Trace | Self::Put | Self::Delete)
+    }
+
     pub fn iterator() -> std::slice::Iter<'static, Method> {
         [Method::Get, Method::Put, Method::Post, Method::Delete, Method::Options,
             Method::Head, Method::Trace, Method::Connect, Method::Patch].iter()
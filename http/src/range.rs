@@ -0,0 +1,358 @@
+use crate::{
+    body::Body,
+    common::CRLF,
+    conditional::Validators,
+    headers::{
+        AcceptRanges, ContentLength, ContentRange, ContentType, Header, HeaderMap, HeaderType,
+        IfRange, Range,
+    },
+    method::Method,
+    response::{IntoResponse, Response},
+    status_code::Status,
+};
+use bytes::{BufMut, Bytes, BytesMut};
+use mime::Mime;
+
+/// More ranges than this are served as the full representation, a client
+/// asking for thousands of tiny ranges gains nothing from them.
+const MAX_RANGES: usize = 64;
+
+/// Ranges overlapping each other more often than this are served as the
+/// full representation, RFC 9110 section 14.2.
+const MAX_OVERLAPS: usize = 2;
+
+/// Which bytes of a representation to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ranges {
+    /// The whole representation with `200 OK`.
+    Full,
+    /// The satisfiable ranges in ascending order, overlapping and adjacent
+    /// ones merged, sent with `206 Partial Content`.
+    Partial(Vec<std::ops::Range<u64>>),
+    /// None of the ranges overlaps the representation, `416 Range Not Satisfiable`.
+    Unsatisfiable,
+}
+
+/// `Range` and `If-Range` of a `GET` request, invalid ones are ignored.
+///
+/// The router applies them to in-memory bodies of successful `GET` responses
+/// and advertises `Accept-Ranges: bytes` on those. A streamed body has no
+/// known length, handlers serving files use [`crate::file::NamedFile`].
+#[derive(Debug, Clone, Default)]
+pub struct RangeRequest {
+    range: Option<Range>,
+    if_range: Option<IfRange>,
+}
+
+impl RangeRequest {
+    pub fn from_request(method: Method, headers: &HeaderMap) -> Self {
+        // a range is only defined for GET, RFC 9110 section 14.2
+        if method != Method::Get {
+            return Self::default();
+        }
+        Self {
+            range: headers.typed_get::<Range>().ok().flatten(),
+            if_range: headers.typed_get::<IfRange>().ok().flatten(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_none()
+    }
+
+    /// The bytes to send of a representation of `length` bytes. With an
+    /// `If-Range` that does not match `current` the whole of it is sent.
+    pub fn resolve(&self, length: u64, current: &Validators) -> Ranges {
+        let Some(range) = &self.range else {
+            return Ranges::Full;
+        };
+        if range.0.len() > MAX_RANGES || !self.if_range_matches(current) {
+            return Ranges::Full;
+        }
+        let mut ranges = range
+            .0
+            .iter()
+            .filter_map(|range| range.bounds(length))
+            .collect::<Vec<_>>();
+        if ranges.is_empty() {
+            return Ranges::Unsatisfiable;
+        }
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<std::ops::Range<u64>> = Vec::with_capacity(ranges.len());
+        let mut overlaps = 0;
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => {
+                    if range.start < last.end {
+                        overlaps += 1;
+                    }
+                    last.end = last.end.max(range.end);
+                }
+                _ => merged.push(range),
+            }
+        }
+        match overlaps > MAX_OVERLAPS {
+            true => Ranges::Full,
+            false => Ranges::Partial(merged),
+        }
+    }
+
+    /// A date only validates when it is the exact `Last-Modified`, an entity
+    /// tag has to match strongly.
+    fn if_range_matches(&self, current: &Validators) -> bool {
+        match (&self.if_range, current) {
+            (None, _) => true,
+            (
+                Some(IfRange::ETag(etag)),
+                Validators {
+                    etag: Some(current),
+                    ..
+                },
+            ) => etag.strong_eq(current),
+            (
+                Some(IfRange::Date(date)),
+                Validators {
+                    last_modified: Some(current),
+                    ..
+                },
+            ) => date == current,
+            _ => false,
+        }
+    }
+
+    /// Serves the requested ranges of an in-memory `200 OK` response,
+    /// other responses are left alone.
+    pub fn apply<'rs>(&self, mut response: Response<'rs>) -> Response<'rs> {
+        let Some(body) = response.body.as_bytes().cloned() else {
+            return response;
+        };
+        if response.status_line.status_code != Status::OK.status_code() {
+            return response;
+        }
+        if !response.headers.contains_key(&HeaderType::AcceptRanges) {
+            response = response.with_header(AcceptRanges::Bytes);
+        }
+        let current = Validators::of(&response.headers);
+        let length = body.len() as u64;
+        match self.resolve(length, &current) {
+            Ranges::Full => response,
+            Ranges::Unsatisfiable => unsatisfiable(length),
+            Ranges::Partial(ranges) => {
+                let pieces = partial(&mut response, &ranges, length);
+                let mut collected = BytesMut::new();
+                for piece in pieces {
+                    match piece {
+                        Piece::Literal(bytes) => collected.put(bytes),
+                        Piece::Segment(range) => {
+                            collected.put(body.slice(range.start as usize..range.end as usize))
+                        }
+                    }
+                }
+                response.body = Body::from(collected.freeze());
+                response
+            }
+        }
+    }
+}
+
+/// Part of a `206` body, either framing of a multipart body or a segment of
+/// the representation.
+pub(crate) enum Piece {
+    Literal(Bytes),
+    Segment(std::ops::Range<u64>),
+}
+
+/// `416 Range Not Satisfiable` for a representation of `length` bytes.
+pub(crate) fn unsatisfiable<'rs>(length: u64) -> Response<'rs> {
+    Status::RangeNotSatisfiable
+        .into_response()
+        .with_header(ContentRange::Unsatisfied(length))
+        .with_header(ContentLength(0))
+}
+
+/// Turns `response` into a `206 Partial Content` with the headers for
+/// `ranges`, the body is left to the caller to assemble from the pieces.
+/// More than one range is sent as `multipart/byteranges`, each part carrying
+/// the original `Content-Type`.
+pub(crate) fn partial(
+    response: &mut Response<'_>,
+    ranges: &[std::ops::Range<u64>],
+    length: u64,
+) -> Vec<Piece> {
+    response.add_status(Status::PartialContent);
+    if let [range] = ranges {
+        response
+            .headers
+            .typed_insert(ContentRange::new(range, length))
+            .expect("a range is a valid field value");
+        response
+            .headers
+            .typed_insert(ContentLength(range.end - range.start))
+            .expect("a length is a valid field value");
+        return vec![Piece::Segment(range.clone())];
+    }
+
+    let boundary = boundary();
+    let content_type = response.headers.remove(&HeaderType::ContentType);
+    let mut pieces = Vec::with_capacity(ranges.len() * 2 + 1);
+    let mut content_length = 0;
+    for (index, range) in ranges.iter().enumerate() {
+        let mut head = BytesMut::new();
+        if index > 0 {
+            head.put(CRLF.as_bytes());
+        }
+        head.put(format!("--{boundary}{CRLF}").as_bytes());
+        if let Some(content_type) = &content_type {
+            head.put(format!("{}: ", HeaderType::ContentType).as_bytes());
+            head.put(content_type.as_bytes());
+            head.put(CRLF.as_bytes());
+        }
+        let content_range = ContentRange::new(range, length);
+        head.put(format!("{}: {content_range}{CRLF}{CRLF}", ContentRange::name()).as_bytes());
+        content_length += head.len() as u64 + (range.end - range.start);
+        pieces.push(Piece::Literal(head.freeze()));
+        pieces.push(Piece::Segment(range.clone()));
+    }
+    let close = Bytes::from(format!("{CRLF}--{boundary}--{CRLF}"));
+    content_length += close.len() as u64;
+    pieces.push(Piece::Literal(close));
+
+    let mime = format!("multipart/byteranges; boundary={boundary}")
+        .parse::<Mime>()
+        .expect("the boundary is a token");
+    response
+        .headers
+        .typed_insert(ContentType(mime))
+        .expect("a parsed media type is a valid field value");
+    response
+        .headers
+        .typed_insert(ContentLength(content_length))
+        .expect("a length is a valid field value");
+    pieces
+}
+
+fn boundary() -> String {
+    let mut random = [0; 12];
+    getrandom::getrandom(&mut random).expect("the system random source is available");
+    random.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::{EntityTag, HeaderValue};
+
+    fn request(headers: &[(HeaderType, &str)]) -> RangeRequest {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name.clone(), HeaderValue::try_from(*value).unwrap());
+        }
+        RangeRequest::from_request(Method::Get, &map)
+    }
+
+    fn ranged(headers: &[(HeaderType, &str)]) -> Response<'static> {
+        request(headers).apply("0123456789".into_response().with_etag())
+    }
+
+    fn header(response: &Response<'_>, name: HeaderType) -> String {
+        let value = response.headers.get_joined(&name).unwrap();
+        value.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_single_and_suffix_ranges() {
+        let full = ranged(&[]);
+        assert_eq!(full.status_line.status_code, 200);
+        assert_eq!(header(&full, HeaderType::AcceptRanges), "bytes");
+
+        let response = ranged(&[(HeaderType::Range, "bytes=2-4")]);
+        assert_eq!(response.status_line.status_code, 206);
+        assert_eq!(response.body, "234");
+        assert_eq!(header(&response, HeaderType::ContentRange), "bytes 2-4/10");
+        assert_eq!(header(&response, HeaderType::ContentLength), "3");
+        assert_eq!(
+            header(&response, HeaderType::ContentType),
+            "text/plain; charset=utf-8"
+        );
+
+        let response = ranged(&[(HeaderType::Range, "bytes=-3")]);
+        assert_eq!(response.body, "789");
+        assert_eq!(header(&response, HeaderType::ContentRange), "bytes 7-9/10");
+
+        let response = ranged(&[(HeaderType::Range, "bytes=8-20")]);
+        assert_eq!(response.body, "89");
+
+        let response = ranged(&[(HeaderType::Range, "bytes=10-")]);
+        assert_eq!(response.status_line.status_code, 416);
+        assert_eq!(header(&response, HeaderType::ContentRange), "bytes */10");
+        assert_eq!(response.body, "");
+
+        // an invalid range is ignored
+        let response = ranged(&[(HeaderType::Range, "bytes=4-2")]);
+        assert_eq!(response.status_line.status_code, 200);
+    }
+
+    #[test]
+    fn test_multiple_ranges() {
+        let response = ranged(&[(HeaderType::Range, "bytes=0-1, 100-200, -2")]);
+        assert_eq!(response.status_line.status_code, 206);
+        let content_type = header(&response, HeaderType::ContentType);
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        let expected = format!(
+            "--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Range: bytes 0-1/10\r\n\r\n01\r\n\
+             --{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Range: bytes 8-9/10\r\n\r\n89\r\n--{boundary}--\r\n"
+        );
+        assert_eq!(response.body, expected.as_str());
+        assert_eq!(
+            header(&response, HeaderType::ContentLength),
+            expected.len().to_string()
+        );
+    }
+
+    #[test]
+    fn test_overlapping_ranges() {
+        let response = ranged(&[(HeaderType::Range, "bytes=4-5, 0-1, 2-3")]);
+        assert_eq!(response.status_line.status_code, 206);
+        assert_eq!(response.body, "012345");
+        assert_eq!(header(&response, HeaderType::ContentRange), "bytes 0-5/10");
+
+        let response = ranged(&[(HeaderType::Range, "bytes=0-3, 2-5, 8-")]);
+        let content_type = header(&response, HeaderType::ContentType);
+        assert!(content_type.starts_with("multipart/byteranges"));
+        let body = response.body.as_bytes().unwrap();
+        assert!(body.windows(6).any(|window| window == b"012345"));
+
+        let repeated = vec!["0-"; MAX_RANGES].join(",");
+        let response = ranged(&[(HeaderType::Range, &format!("bytes={repeated}"))]);
+        assert_eq!(response.status_line.status_code, 200);
+        assert_eq!(response.body, "0123456789");
+    }
+
+    #[test]
+    fn test_if_range() {
+        let etag = EntityTag::strong_from_body(b"0123456789").to_string();
+        let response = ranged(&[
+            (HeaderType::Range, "bytes=0-0"),
+            (HeaderType::IfRange, &etag),
+        ]);
+        assert_eq!(response.status_line.status_code, 206);
+
+        let response = ranged(&[
+            (HeaderType::Range, "bytes=0-0"),
+            (HeaderType::IfRange, "\"stale\""),
+        ]);
+        assert_eq!(response.status_line.status_code, 200);
+        assert_eq!(response.body, "0123456789");
+
+        // without a Last-Modified a date never validates
+        let response = ranged(&[
+            (HeaderType::Range, "bytes=0-0"),
+            (HeaderType::IfRange, "Sun, 06 Nov 1994 08:49:37 GMT"),
+        ]);
+        assert_eq!(response.status_line.status_code, 200);
+    }
+}
@@ -1,11 +1,20 @@
-use std::collections::BTreeMap;
-use crate::{common::RhttpError, headers::{HeaderType, HeaderValue}, method::Method, version::ProtocolVersion};
+use crate::{
+    common::{
+        RhttpError::{self, InvalidFraming},
+        CRLF,
+    },
+    framing::{content_length, encode_chunked, last_transfer_coding, Strictness},
+    headers::{HeaderMap, HeaderType, HeaderValue},
+    method::Method,
+    version::ProtocolVersion,
+};
+use bytes::{BufMut, Bytes, BytesMut};
 
 #[derive(Debug, Default, Clone)]
 pub struct Request {
-    pub request_line : RequestLine,
-    pub headers: BTreeMap<HeaderType, HeaderValue>,
-    pub body: String
+    pub request_line: RequestLine,
+    pub headers: HeaderMap,
+    pub body: Bytes,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -32,10 +41,214 @@ impl Request {
         self.request_line.protocol_version = protocol_version;
     }
 
+    /// Appends the header, values already set for the name are kept.
     pub fn add_header(&mut self, key: String, val: String) -> Result<(), RhttpError> {
         let header_type = HeaderType::from_string(key)?;
         let header_value = val.parse::<HeaderValue>()?;
-        self.headers.insert(header_type, header_value);
+        self.headers.append(header_type, header_value);
         Ok(())
     }
+
+    /// Serializes the request with [`Strictness::Strict`] framing validation.
+    pub fn serialize(&self) -> Result<BytesMut, RhttpError> {
+        self.serialize_with(Strictness::Strict)
+    }
+
+    /// Serializes the request after checking its framing headers agree with the body.
+    ///
+    /// A `Content-Length` that doesn't match the body or that is sent together
+    /// with `Transfer-Encoding` is an error when strict and gets corrected when
+    /// lenient. With `Transfer-Encoding: chunked` the body is chunk encoded,
+    /// other final codings can't delimit a request and are always rejected.
+    pub fn serialize_with(&self, strictness: Strictness) -> Result<BytesMut, RhttpError> {
+        self.headers.validate()?;
+        self.validate_target()?;
+        let request_line = &self.request_line;
+        if strictness == Strictness::Strict
+            && request_line.protocol_version == ProtocolVersion::Http11
+            && !self.headers.contains_key(&HeaderType::Host)
+        {
+            return Err(InvalidFraming(String::from(
+                "HTTP/1.1 request without Host header",
+            )));
+        }
+
+        let transfer_encoding = HeaderType::TransferEncoding;
+        let chunked = match self.headers.get_joined(&transfer_encoding) {
+            Some(value) => match last_transfer_coding(value.as_bytes()) {
+                Some(coding) if coding.eq_ignore_ascii_case("chunked") => true,
+                _ => {
+                    return Err(InvalidFraming(format!(
+                        "request Transfer-Encoding {value:?} does not end with chunked"
+                    )))
+                }
+            },
+            None => false,
+        };
+        let declared_length = content_length(
+            self.headers
+                .get_all(&HeaderType::ContentLength)
+                .map(HeaderValue::as_bytes),
+        )?;
+        let content_length = match (chunked, declared_length) {
+            (true, Some(_)) if strictness == Strictness::Strict => {
+                return Err(InvalidFraming(String::from(
+                    "both Transfer-Encoding and Content-Length are present",
+                )))
+            }
+            (true, _) => None,
+            (false, Some(length))
+                if length != self.body.len() && strictness == Strictness::Strict =>
+            {
+                return Err(InvalidFraming(format!(
+                    "Content-Length {length} does not match body length {}",
+                    self.body.len()
+                )))
+            }
+            (false, Some(_)) => Some(self.body.len()),
+            (false, None) => {
+                let expects_body = matches!(
+                    request_line.method,
+                    Method::Post | Method::Put | Method::Patch
+                );
+                (expects_body || !self.body.is_empty()).then_some(self.body.len())
+            }
+        };
+
+        let mut return_buff = self.serialize_request_line();
+        self.headers
+            .iter()
+            .filter(|(header_type, _)| **header_type != HeaderType::ContentLength)
+            .for_each(|(header_type, header_value)| {
+                return_buff.put(format!("{header_type}: ").as_bytes());
+                return_buff.put(header_value.as_bytes());
+                return_buff.put(CRLF.as_bytes());
+            });
+        if let Some(content_length) = content_length {
+            return_buff
+                .put(format!("{}: {}{CRLF}", HeaderType::ContentLength, content_length).as_bytes());
+        }
+        return_buff.put(CRLF.as_bytes());
+        if chunked {
+            encode_chunked(&self.body, &mut return_buff);
+        } else {
+            return_buff.put(self.body.as_ref());
+        }
+        Ok(return_buff)
+    }
+
+    /// Serializes the request line and header section as they are, for a
+    /// body written after them with the framing the headers declare.
+    pub fn serialize_head(&self) -> Result<BytesMut, RhttpError> {
+        self.headers.validate()?;
+        self.validate_target()?;
+        let mut return_buff = self.serialize_request_line();
+        self.headers.iter().for_each(|(header_type, header_value)| {
+            return_buff.put(format!("{header_type}: ").as_bytes());
+            return_buff.put(header_value.as_bytes());
+            return_buff.put(CRLF.as_bytes());
+        });
+        return_buff.put(CRLF.as_bytes());
+        Ok(return_buff)
+    }
+
+    fn validate_target(&self) -> Result<(), RhttpError> {
+        let path = &self.request_line.path;
+        if path.is_empty()
+            || path
+                .bytes()
+                .any(|b| b.is_ascii_whitespace() || b.is_ascii_control())
+        {
+            return Err(InvalidFraming(format!("invalid request target {path:?}")));
+        }
+        Ok(())
+    }
+
+    fn serialize_request_line(&self) -> BytesMut {
+        let request_line = &self.request_line;
+        let mut return_buff = BytesMut::new();
+        return_buff.put(
+            format!(
+                "{} {} {}{CRLF}",
+                request_line.method.to_str(),
+                request_line.path,
+                request_line.protocol_version
+            )
+            .as_bytes(),
+        );
+        return_buff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, body: &'static str) -> Request {
+        let mut request = Request::new();
+        request.add_method(method);
+        request.add_path(String::from("/order/1?activity=2"));
+        request
+            .add_header(String::from("Host"), String::from("example.test"))
+            .expect("valid header");
+        request.body = Bytes::from_static(body.as_bytes());
+        request
+    }
+
+    #[test]
+    fn test_serialize_adds_content_length() {
+        let wire = request(Method::Post, "{}")
+            .serialize()
+            .expect("valid request");
+        assert_eq!(
+            wire,
+            "POST /order/1?activity=2 HTTP/1.1\r\nHost: example.test\r\nContent-Length: 2\r\n\r\n{}"
+        );
+        let wire = request(Method::Get, "").serialize().expect("valid request");
+        assert_eq!(
+            wire,
+            "GET /order/1?activity=2 HTTP/1.1\r\nHost: example.test\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_chunked() {
+        let mut request = request(Method::Put, "hello");
+        request
+            .add_header(String::from("Transfer-Encoding"), String::from("chunked"))
+            .expect("valid header");
+        let wire = request.serialize().expect("valid request");
+        assert!(wire.ends_with(b"Transfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"));
+
+        request
+            .add_header(String::from("Content-Length"), String::from("5"))
+            .expect("valid header");
+        assert!(matches!(request.serialize(), Err(InvalidFraming(_))));
+        let wire = request
+            .serialize_with(Strictness::Lenient)
+            .expect("lenient drops Content-Length");
+        assert!(!String::from_utf8_lossy(&wire).contains("Content-Length"));
+    }
+
+    #[test]
+    fn test_serialize_validates_framing() {
+        let mut request = request(Method::Post, "four");
+        request
+            .add_header(String::from("Content-Length"), String::from("10"))
+            .expect("valid header");
+        assert!(matches!(request.serialize(), Err(InvalidFraming(_))));
+        let wire = request
+            .serialize_with(Strictness::Lenient)
+            .expect("lenient corrects Content-Length");
+        assert!(wire.ends_with(b"Content-Length: 4\r\n\r\nfour"));
+
+        let mut request = self::request(Method::Get, "");
+        request.add_path(String::from("/a b"));
+        assert!(request.serialize().is_err());
+
+        let mut request = self::request(Method::Get, "");
+        request.headers.remove(&HeaderType::Host);
+        assert!(request.serialize().is_err());
+        assert!(request.serialize_with(Strictness::Lenient).is_ok());
+    }
 }
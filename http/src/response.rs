@@ -1,18 +1,32 @@
 use crate::{
-    common::{RhttpError, CRLF},
-    headers::{HeaderType, HeaderValue},
+    body::Body,
+    common::{
+        RhttpError::{self, InvalidFraming, ParsingResponseErr},
+        CRLF,
+    },
+    cookie::{Cookie, CookieJar, PrivateCookieJar, SignedCookieJar},
+    framing::{
+        decode_chunked, head_length, parse_response_head, write_pieces, BodyFraming, ParseOptions,
+        ResponseHead, Strictness,
+    },
+    headers::{
+        Accept, ContentLength, ContentType, ETag, EntityTag, Header, HeaderMap, HeaderType,
+        HeaderValue, Vary,
+    },
     status_code::Status,
     version::ProtocolVersion,
 };
-use bytes::{BufMut, BytesMut};
-use chrono::Utc;
-use std::collections::BTreeMap;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{AsyncWrite, AsyncWriteExt};
+use mime::Mime;
+use serde::Serialize;
+use std::str;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Response<'rs> {
     pub status_line: StatusLine<'rs>,
-    pub headers: BTreeMap<HeaderType, HeaderValue>,
-    pub body: String,
+    pub headers: HeaderMap,
+    pub body: Body,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -32,21 +46,6 @@ impl Default for StatusLine<'_> {
     }
 }
 
-impl Default for Response<'_> {
-    fn default() -> Self {
-        let mut zelf = Self {
-            status_line: StatusLine::default(),
-            headers: BTreeMap::default(),
-            body: String::default(),
-        };
-        zelf.add_header(
-            HeaderType::Date,
-            Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
-        );
-        zelf
-    }
-}
-
 pub trait IntoResponse {
     fn into_response<'rs>(self) -> Response<'rs>;
 }
@@ -54,20 +53,73 @@ pub trait IntoResponse {
 pub struct Html(pub String);
 
 impl Response<'_> {
-    fn add_header<T>(&mut self, key: HeaderType, val: T)
-    where
-        T: TryInto<HeaderValue, Error = RhttpError>,
-    {
-        let header_value = val.try_into().unwrap();
-        self.headers.entry(key).or_insert(header_value);
+    /// Appends the header, values already set for the name are kept. Fails
+    /// when the name is not a token or the value contains control characters.
+    pub fn add_header(&mut self, key: String, val: String) -> Result<(), RhttpError> {
+        let header_type = HeaderType::from_string(key)?;
+        let header_value = val.parse::<HeaderValue>()?;
+        self.headers.append(header_type, header_value);
+        Ok(())
+    }
+
+    /// Sets a typed header, replacing any value it had. A header that doesn't
+    /// encode to a valid field value turns the response into `500 Internal
+    /// Server Error` rather than splitting it.
+    pub fn with_header<H: Header>(mut self, header: H) -> Self {
+        match self.headers.typed_insert(header) {
+            Ok(()) => self,
+            Err(_) => Status::InternalServerError.into_response(),
+        }
+    }
+
+    /// Appends a `Set-Cookie` header, one per cookie.
+    pub fn with_cookie(mut self, cookie: &Cookie) -> Self {
+        self.headers.append(HeaderType::SetCookie, cookie.encode());
+        self
+    }
+
+    /// Sets a strong `ETag` hashed from the body, a streamed body is left
+    /// without one.
+    pub fn with_etag(self) -> Self {
+        match self
+            .body
+            .as_bytes()
+            .map(|body| EntityTag::strong_from_body(body))
+        {
+            Some(etag) => self.with_header(ETag(etag)),
+            None => self,
+        }
+    }
+
+    /// Sets a weak `ETag` hashed from the body, for representations that are
+    /// equivalent but not byte for byte identical.
+    pub fn with_weak_etag(self) -> Self {
+        match self
+            .body
+            .as_bytes()
+            .map(|body| EntityTag::weak_from_body(body))
+        {
+            Some(etag) => self.with_header(ETag(etag)),
+            None => self,
+        }
     }
 
-    fn add_status(&mut self, status: Status) {
+    pub(crate) fn add_status(&mut self, status: Status) {
         self.status_line.status_code = status.status_code();
         self.status_line.reason_phrase = status.as_str();
     }
 
+    /// Serializes the whole message, a streamed body is left out, it can only
+    /// be sent with [`Response::write_to`].
     pub fn serialize(&self) -> BytesMut {
+        let mut return_buff = self.serialize_head();
+        if let Body::Full(body) = &self.body {
+            return_buff.put(body.as_ref());
+        }
+        return_buff
+    }
+
+    fn serialize_head(&self) -> BytesMut {
         let mut return_buff = BytesMut::new();
 
         return_buff.put(
@@ -80,32 +132,108 @@ impl Response<'_> {
             .as_bytes(),
         );
         self.headers.iter().for_each(|(header_type, header_value)| {
-            return_buff
-                .put(format!("{}: {}{}", header_type, header_value.to_str(), CRLF).as_bytes())
+            return_buff.put(format!("{header_type}: ").as_bytes());
+            return_buff.put(header_value.as_bytes());
+            return_buff.put(CRLF.as_bytes());
         });
         return_buff.put(CRLF.as_bytes());
-        return_buff.put(self.body.as_bytes());
 
         return_buff
     }
+
+    /// Writes the message to `writer`. A streamed body is written as it is
+    /// produced, chunk encoded unless a `Content-Length` is set.
+    pub async fn write_to<W: AsyncWrite + Unpin>(
+        mut self,
+        writer: &mut W,
+    ) -> Result<(), RhttpError> {
+        self.headers.validate()?;
+        let stream = match std::mem::take(&mut self.body) {
+            Body::Full(body) => {
+                self.body = Body::Full(body);
+                writer.write_all(&self.serialize()).await?;
+                return Ok(writer.flush().await?);
+            }
+            Body::Stream(stream) => stream,
+        };
+
+        let chunked = !self.headers.contains_key(&HeaderType::ContentLength);
+        if chunked {
+            self.headers.insert(
+                HeaderType::TransferEncoding,
+                HeaderValue::try_from("chunked")?,
+            );
+        }
+        writer.write_all(&self.serialize_head()).await?;
+        write_pieces(writer, stream, chunked).await
+    }
+
+    /// Parses exactly one complete response with the default (strict) options.
+    pub fn parse(bytes: &[u8]) -> Result<Response<'static>, RhttpError> {
+        Response::parse_with(bytes, &ParseOptions::default())
+    }
+
+    /// Parses exactly one complete response. The reason phrase on the wire is
+    /// replaced by the registered one for the status code.
+    pub fn parse_with(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Response<'static>, RhttpError> {
+        let head_length = head_length(bytes).ok_or(ParsingResponseErr(String::from(
+            "incomplete header section",
+        )))?;
+        let ResponseHead {
+            status_line,
+            headers,
+            framing,
+        } = parse_response_head(&bytes[..head_length], options)?;
+
+        let rest = &bytes[head_length..];
+        let (body, consumed) = match framing {
+            BodyFraming::Empty => (Bytes::new(), 0),
+            BodyFraming::ContentLength(length) => {
+                let body = rest.get(..length).ok_or(ParsingResponseErr(format!(
+                    "body is shorter than Content-Length {length}"
+                )))?;
+                (Bytes::copy_from_slice(body), length)
+            }
+            BodyFraming::Chunked => decode_chunked(rest, options.strictness)?,
+            BodyFraming::CloseDelimited => (Bytes::copy_from_slice(rest), rest.len()),
+        };
+        if options.strictness == Strictness::Strict && consumed < rest.len() {
+            return Err(InvalidFraming(format!(
+                "{} bytes after the end of the message",
+                rest.len() - consumed
+            )));
+        }
+
+        Ok(Response {
+            status_line,
+            headers,
+            body: Body::Full(body),
+        })
+    }
+}
+
+impl IntoResponse for Response<'static> {
+    fn into_response<'rs>(self) -> Response<'rs> {
+        self
+    }
 }
 
 impl IntoResponse for Html {
     fn into_response<'rs>(self) -> Response<'rs> {
-        let mut resp = Response {
-            body: self.0,
+        Response {
+            body: Body::from(self.0),
             ..Response::default()
-        };
-        resp.add_header(HeaderType::ContentType, mime::TEXT_HTML_UTF_8.as_ref());
-        resp
+        }
+        .with_header(ContentType(mime::TEXT_HTML_UTF_8))
     }
 }
 
 impl IntoResponse for () {
     fn into_response<'rs>(self) -> Response<'rs> {
-        let mut resp = Response::default();
-        resp.add_header(HeaderType::ContentLength, 0);
-        resp
+        Response::default().with_header(ContentLength(0))
     }
 }
 
@@ -130,28 +258,420 @@ where
 
 impl IntoResponse for &str {
     fn into_response<'rs>(self) -> Response<'rs> {
-        let mut resp = Response {
+        Response {
             status_line: StatusLine::default(),
-            headers: BTreeMap::default(),
-            body: self.to_string(),
-        };
-        resp.add_header(HeaderType::ContentLength, self.len());
-        resp.add_header(HeaderType::ContentType, mime::TEXT_PLAIN_UTF_8.as_ref());
-
-        resp
+            headers: HeaderMap::default(),
+            body: Body::Full(Bytes::copy_from_slice(self.as_bytes())),
+        }
+        .with_header(ContentLength(self.len() as u64))
+        .with_header(ContentType(mime::TEXT_PLAIN_UTF_8))
     }
 }
 
 impl IntoResponse for String {
     fn into_response<'rs>(self) -> Response<'rs> {
-        let mut resp = Response {
+        let content_length = self.len();
+        Response {
             status_line: StatusLine::default(),
-            headers: BTreeMap::default(),
-            body: self.to_string(),
+            headers: HeaderMap::default(),
+            body: Body::from(self),
+        }
+        .with_header(ContentLength(content_length as u64))
+        .with_header(ContentType(mime::TEXT_PLAIN_UTF_8))
+    }
+}
+
+/// Sends the cookies added to or removed from the jar along with the response.
+impl<T> IntoResponse for (CookieJar, T)
+where
+    T: IntoResponse,
+{
+    fn into_response<'rs>(self) -> Response<'rs> {
+        self.0
+            .delta()
+            .fold(self.1.into_response(), Response::with_cookie)
+    }
+}
+
+impl<T> IntoResponse for (SignedCookieJar, T)
+where
+    T: IntoResponse,
+{
+    fn into_response<'rs>(self) -> Response<'rs> {
+        (CookieJar::from(self.0), self.1).into_response()
+    }
+}
+
+impl<T> IntoResponse for (PrivateCookieJar, T)
+where
+    T: IntoResponse,
+{
+    fn into_response<'rs>(self) -> Response<'rs> {
+        (CookieJar::from(self.0), self.1).into_response()
+    }
+}
+
+type Renderer<T> = Box<dyn Fn(&T) -> String + Send + Sync>;
+
+/// Serializes a value in the representation the client prefers by its
+/// `Accept` header: JSON, a URL-encoded form, plain text or, with a renderer
+/// set, HTML. A representation the value cannot be serialized to, such as a
+/// URL-encoded form of a sequence, is not offered. Without an acceptable
+/// representation the configured fallback is sent, or `406 Not Acceptable`
+/// when there is none.
+pub struct Negotiate<T> {
+    accept: Accept,
+    value: T,
+    html: Option<Renderer<T>>,
+    fallback: Option<Mime>,
+}
+
+impl<T: Serialize> Negotiate<T> {
+    pub fn new(accept: Accept, value: T) -> Self {
+        Self {
+            accept,
+            value,
+            html: None,
+            fallback: None,
+        }
+    }
+
+    /// Offers `text/html` rendered by `render`.
+    pub fn html(mut self, render: impl Fn(&T) -> String + Send + Sync + 'static) -> Self {
+        self.html = Some(Box::new(render));
+        self
+    }
+
+    /// Representation preferred on a tie and sent when the client accepts
+    /// none, one of `application/json`, `application/x-www-form-urlencoded`,
+    /// `text/plain` or `text/html`. A representation that is not offered is
+    /// ignored, so a client accepting none of the offered ones gets 406.
+    pub fn fallback(mut self, mime: Mime) -> Self {
+        self.fallback = Some(mime);
+        self
+    }
+
+    fn available(&self) -> Vec<Mime> {
+        let mut available = vec![
+            mime::APPLICATION_JSON,
+            mime::APPLICATION_WWW_FORM_URLENCODED,
+            mime::TEXT_PLAIN_UTF_8,
+        ];
+        if self.html.is_some() {
+            available.insert(1, mime::TEXT_HTML_UTF_8);
+        }
+        if let Some(fallback) = &self.fallback {
+            if let Some(position) = available
+                .iter()
+                .position(|mime| mime.essence_str() == fallback.essence_str())
+            {
+                let fallback = available.remove(position);
+                available.insert(0, fallback);
+            }
+        }
+        available
+    }
+
+    fn render(&self, mime: &Mime) -> Option<String> {
+        match (mime.type_(), mime.subtype()) {
+            (mime::APPLICATION, mime::JSON) => serde_json::to_string(&self.value).ok(),
+            // only maps and structs make a form, serde_qs numbers sequence items
+            (mime::APPLICATION, mime::WWW_FORM_URLENCODED) => {
+                match serde_json::to_value(&self.value).ok()? {
+                    serde_json::Value::Object(_) => serde_qs::to_string(&self.value).ok(),
+                    _ => None,
+                }
+            }
+            (mime::TEXT, mime::HTML) => self.html.as_ref().map(|render| render(&self.value)),
+            (mime::TEXT, mime::PLAIN) => match serde_json::to_value(&self.value).ok()? {
+                serde_json::Value::String(text) => Some(text),
+                value @ (serde_json::Value::Object(_) | serde_json::Value::Array(_)) => {
+                    serde_json::to_string_pretty(&value).ok()
+                }
+                value => Some(value.to_string()),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Negotiate<T> {
+    fn into_response<'rs>(self) -> Response<'rs> {
+        let mut available = self.available();
+        let response = loop {
+            let chosen = self.accept.negotiate(&available).or_else(|| {
+                let fallback = self.fallback.as_ref()?;
+                available
+                    .iter()
+                    .find(|mime| mime.essence_str() == fallback.essence_str())
+            });
+            let Some(mime) = chosen.cloned() else {
+                break Status::NotAcceptable.into_response();
+            };
+            match self.render(&mime) {
+                Some(body) => break body.into_response().with_header(ContentType(mime)),
+                None => available.retain(|offered| *offered != mime),
+            }
         };
-        resp.add_header(HeaderType::ContentLength, self.len());
-        resp.add_header(HeaderType::ContentType, mime::TEXT_PLAIN_UTF_8.as_ref());
+        response.with_header(Vary::Headers(vec![HeaderType::Accept]))
+    }
+}
 
-        resp
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_length() {
+        let response = Response::parse(
+            b"HTTP/1.1 404 Whatever\r\nContent-Length: 5\r\nServer: x\r\n\r\nhello",
+        )
+        .expect("valid response");
+        assert_eq!(response.status_line.status_code, 404);
+        assert_eq!(response.status_line.reason_phrase, "Not Found");
+        assert_eq!(response.body, "hello");
+        assert_eq!(
+            response
+                .headers
+                .get(&HeaderType::ContentLength)
+                .map(|value| value.to_str().unwrap()),
+            Some("5")
+        );
+    }
+
+    #[test]
+    fn test_parse_chunked_and_head() {
+        let response = Response::parse(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+        )
+        .expect("valid response");
+        assert_eq!(response.body, "abc");
+
+        let options = ParseOptions::new().head_request(true);
+        let response =
+            Response::parse_with(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n", &options)
+                .expect("valid response");
+        assert_eq!(response.body, "");
+    }
+
+    #[test]
+    fn test_parse_strict_rejects_what_lenient_recovers() {
+        let lenient = ParseOptions::new().strictness(Strictness::Lenient);
+        let smuggling: &[u8] =
+            b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\n0\r\n\r\n";
+        assert!(matches!(Response::parse(smuggling), Err(InvalidFraming(_))));
+        assert_eq!(
+            Response::parse_with(smuggling, &lenient)
+                .expect("lenient")
+                .body,
+            "a"
+        );
+
+        let bare_lf: &[u8] = b"HTTP/1.1 200 OK\nContent-Length: 2\n\nok";
+        assert!(Response::parse(bare_lf).is_err());
+        assert_eq!(
+            Response::parse_with(bare_lf, &lenient)
+                .expect("lenient")
+                .body,
+            "ok"
+        );
+
+        let folded: &[u8] = b"HTTP/1.1 204 No Content\r\nX-Long: a\r\n b\r\n\r\n";
+        assert!(Response::parse(folded).is_err());
+        let response = Response::parse_with(folded, &lenient).expect("lenient");
+        assert_eq!(
+            response
+                .headers
+                .get(&HeaderType::Custom(String::from("X-Long")))
+                .map(|value| value.to_str().unwrap()),
+            Some("a b")
+        );
+
+        let trailing: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nokHTTP/1.1";
+        assert!(matches!(Response::parse(trailing), Err(InvalidFraming(_))));
+        assert!(Response::parse_with(trailing, &lenient).is_ok());
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_framing() {
+        assert!(Response::parse(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort").is_err());
+        assert!(Response::parse(b"HTTP/1.1 200 OK\r\nContent-Length: 1, 2\r\n\r\na").is_err());
+        assert!(Response::parse(b"HTTP/1.1 200 OK\r\nContent-Length: -1\r\n\r\n").is_err());
+        assert!(Response::parse(b"HTTP/1.1 2000 OK\r\n\r\n").is_err());
+        assert!(Response::parse(b"HTTP/1.1 200 OK\r\nContent-Length : 0\r\n\r\n").is_err());
+        let huge_chunk: &[u8] =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\nx\r\n0\r\n\r\n";
+        assert!(matches!(
+            Response::parse(huge_chunk),
+            Err(InvalidFraming(_))
+        ));
+    }
+
+    #[test]
+    fn test_serialize_parse_round_trip() {
+        let response = String::from("round trip").into_response();
+        let parsed = Response::parse(&response.serialize()).expect("valid response");
+        assert_eq!(parsed, response);
+    }
+
+    #[test]
+    fn test_repeated_headers_round_trip_in_order() {
+        let wire: &[u8] =
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nContent-Length: 0\r\nSet-Cookie: b=2\r\n\r\n";
+        let response = Response::parse(wire).expect("valid response");
+        let set_cookie = HeaderType::Custom(String::from("Set-Cookie"));
+        let cookies: Vec<&str> = response
+            .headers
+            .get_all(&set_cookie)
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert_eq!(response.serialize(), wire);
+    }
+
+    #[test]
+    fn test_obs_text_value_round_trips() {
+        let wire: &[u8] =
+            b"HTTP/1.1 200 OK\r\nContent-Disposition: attachment; filename=\"caf\xe9.txt\"\r\nContent-Length: 0\r\n\r\n";
+        let response = Response::parse(wire).expect("valid response");
+        let disposition = response
+            .headers
+            .get(&HeaderType::Custom(String::from("Content-Disposition")))
+            .unwrap();
+        assert_eq!(
+            disposition.as_bytes(),
+            b"attachment; filename=\"caf\xe9.txt\""
+        );
+        assert!(disposition.to_str().is_err());
+        assert_eq!(response.serialize(), wire);
+    }
+
+    #[async_std::test]
+    async fn test_add_header_rejects_injection() {
+        let mut response = "ok".into_response();
+        assert!(response
+            .add_header(String::from("X-Echo"), String::from("a\r\nSet-Cookie: b=1"))
+            .is_err());
+        assert!(response
+            .add_header(String::from("X-Echo"), String::from("a"))
+            .is_ok());
+
+        response.headers.append(
+            HeaderType::Custom(String::from("X-Echo\r\nSet-Cookie")),
+            HeaderValue::try_from("b=1").unwrap(),
+        );
+        let mut written = Vec::new();
+        assert!(response.write_to(&mut written).await.is_err());
+        assert!(written.is_empty());
+
+        let vary = Vary::Headers(vec![HeaderType::Custom(String::from("X\r\nSet-Cookie"))]);
+        let response = "ok".into_response().with_header(vary);
+        assert_eq!(response.status_line.status_code, 500);
+        assert!(!response.serialize().windows(2).any(|pair| pair == b"\r\nS"));
+    }
+
+    #[derive(Serialize)]
+    struct Order {
+        id: u32,
+        item: &'static str,
+    }
+
+    fn negotiate(accept: &str) -> Response<'static> {
+        let mut headers = HeaderMap::new();
+        headers.append(HeaderType::Accept, HeaderValue::try_from(accept).unwrap());
+        let accept = headers.typed_get::<Accept>().unwrap().unwrap_or_default();
+        Negotiate::new(accept, Order { id: 1, item: "tea" })
+            .html(|order| format!("<p>{}</p>", order.item))
+            .into_response()
+    }
+
+    fn content_type(response: &Response<'_>) -> String {
+        let ContentType(mime) = response.headers.typed_get().unwrap().unwrap();
+        mime.essence_str().to_owned()
+    }
+
+    #[test]
+    fn test_negotiate_picks_representation() {
+        let response = negotiate("*/*");
+        assert_eq!(content_type(&response), "application/json");
+        assert_eq!(response.body, r#"{"id":1,"item":"tea"}"#);
+        assert_eq!(
+            response.headers.typed_get::<Vary>().unwrap(),
+            Some(Vary::Headers(vec![HeaderType::Accept]))
+        );
+
+        let response = negotiate("text/html, application/json;q=0.9");
+        assert_eq!(content_type(&response), "text/html");
+        assert_eq!(response.body, "<p>tea</p>");
+
+        let response = negotiate("application/x-www-form-urlencoded");
+        assert_eq!(response.body, "id=1&item=tea");
+
+        let response = negotiate("text/plain");
+        assert_eq!(content_type(&response), "text/plain");
+        assert_eq!(
+            Negotiate::new(Accept::default(), "tea")
+                .fallback(mime::TEXT_PLAIN)
+                .into_response()
+                .body,
+            "tea"
+        );
+
+        let response = negotiate("image/png");
+        assert_eq!(response.status_line.status_code, 406);
+        assert!(response.headers.contains_key(&HeaderType::Vary));
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            HeaderType::Accept,
+            HeaderValue::try_from("image/png").unwrap(),
+        );
+        let accept = headers.typed_get::<Accept>().unwrap().unwrap();
+        let fallback = Negotiate::new(accept.clone(), 1)
+            .fallback(mime::TEXT_PLAIN)
+            .into_response();
+        assert_eq!(fallback.status_line.status_code, 200);
+        assert_eq!(content_type(&fallback), "text/plain");
+
+        let unsupported = Negotiate::new(accept.clone(), 1)
+            .fallback(mime::TEXT_HTML)
+            .into_response();
+        assert_eq!(unsupported.status_line.status_code, 406);
+        let unsupported = Negotiate::new(accept, 1)
+            .fallback(mime::IMAGE_PNG)
+            .into_response();
+        assert_eq!(unsupported.status_line.status_code, 406);
+    }
+
+    #[test]
+    fn test_negotiate_skips_representations_the_value_lacks() {
+        let accept = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.append(HeaderType::Accept, HeaderValue::try_from(value).unwrap());
+            headers.typed_get::<Accept>().unwrap().unwrap()
+        };
+
+        let form = accept("application/x-www-form-urlencoded");
+        let response = Negotiate::new(form.clone(), "tea").into_response();
+        assert_eq!(response.status_line.status_code, 406);
+        let response = Negotiate::new(form.clone(), vec![1, 2]).into_response();
+        assert_eq!(response.status_line.status_code, 406);
+        let response = Negotiate::new(form, "tea")
+            .fallback(mime::TEXT_PLAIN)
+            .into_response();
+        assert_eq!(content_type(&response), "text/plain");
+        assert_eq!(response.body, "tea");
+        let response = Negotiate::new(accept("application/x-www-form-urlencoded"), 1)
+            .fallback(mime::APPLICATION_WWW_FORM_URLENCODED)
+            .into_response();
+        assert_eq!(response.status_line.status_code, 406);
+
+        let response = Negotiate::new(
+            accept("application/x-www-form-urlencoded, application/json;q=0.5"),
+            vec![1, 2],
+        )
+        .into_response();
+        assert_eq!(content_type(&response), "application/json");
+        assert_eq!(response.body, "[1,2]");
     }
 }
@@ -198,7 +198,11 @@ impl  Status {
     }
 
     pub fn status_message(status_code: usize) -> Self {
-        match status_code {
+        Self::from_status_code(status_code).expect("No other status code exists")
+    }
+
+    pub fn from_status_code(status_code: usize) -> Option<Self> {
+        let status = match status_code {
            100 => Status::Continue,
            101 => Status::SwitchingProtocols,
            102 => Status::Processing,
@@ -260,7 +264,8 @@ impl  Status {
            508 => Status::LoopDetected,
            510 => Status::NotExtended,
            511 => Status::NetworkAuthenticationRequired,
-             _ => unreachable!("No other status code exists")
-        }
+             _ => return None
+        };
+        Some(status)
     }
 }
@@ -1,3 +1,419 @@
+use crate::{
+    common::RhttpError::{self, InvalidUrl},
+    method::Method,
+};
+use std::{fmt::Formatter, net::Ipv6Addr, str::FromStr};
+
+/// The four forms of a request target, RFC 9112 section 3.2.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TargetForm {
+    /// `/path?query`, what is sent to an origin server.
+    #[default]
+    Origin,
+    /// `http://example.test/path?query`, what is sent to a proxy.
+    Absolute,
+    /// `example.test:443`, only for `CONNECT`.
+    Authority,
+    /// `*`, only for an `OPTIONS` request about the whole server.
+    Asterisk,
+}
+
+/// A request target, RFC 3986 and RFC 9112 section 3.2.
+///
+/// Path and query are kept percent-encoded, normalized as RFC 3986 section
+/// 6.2.2 describes: escapes of unreserved characters are decoded and the hex
+/// digits of the others uppercased. [`Url::path_segments`] and
+/// [`Url::query_pairs`] decode them.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Url {
+    form: TargetForm,
+    scheme: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    path: String,
+    query: Option<String>,
+}
+
+impl Default for Url {
+    fn default() -> Self {
+        Self {
+            form: TargetForm::Origin,
+            scheme: None,
+            host: None,
+            port: None,
+            path: String::from("/"),
+            query: None,
+        }
+    }
+}
+
+impl Url {
+    /// Parses the target of a request line. The authority-form is only
+    /// valid for `CONNECT` and the asterisk-form only for `OPTIONS`.
+    /// Clients never send a fragment, a target with one is rejected.
+    pub fn parse_request_target(method: Method, target: &str) -> Result<Self, RhttpError> {
+        if target.contains('#') {
+            return Err(invalid(target, "fragment"));
+        }
+        match target {
+            "*" if method == Method::Options => Ok(Self {
+                form: TargetForm::Asterisk,
+                path: String::from("*"),
+                ..Self::default()
+            }),
+            "*" => Err(invalid(target, "asterisk-form outside of OPTIONS")),
+            _ if target.starts_with('/') => {
+                let (path, query) = split_query(target)?;
+                Ok(Self {
+                    path,
+                    query,
+                    ..Self::default()
+                })
+            }
+            _ if method == Method::Connect => {
+                let (host, port) = parse_authority(target)?;
+                Ok(Self {
+                    form: TargetForm::Authority,
+                    host: Some(host),
+                    port: Some(port.ok_or_else(|| invalid(target, "missing port"))?),
+                    path: String::new(),
+                    ..Self::default()
+                })
+            }
+            _ => Self::parse_absolute(target),
+        }
+    }
+
+    /// `scheme://authority/path?query`, an empty path is `/`.
+    fn parse_absolute(target: &str) -> Result<Self, RhttpError> {
+        let (scheme, rest) = target
+            .split_once("://")
+            .ok_or_else(|| invalid(target, "relative reference"))?;
+        if !is_scheme(scheme) {
+            return Err(invalid(target, "scheme"));
+        }
+        let (authority, rest) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+        let (host, port) = parse_authority(authority)?;
+        let (path, query) = split_query(rest)?;
+        Ok(Self {
+            form: TargetForm::Absolute,
+            scheme: Some(scheme.to_ascii_lowercase()),
+            host: Some(host),
+            port,
+            path: if path.is_empty() {
+                String::from("/")
+            } else {
+                path
+            },
+            query,
+        })
+    }
+
+    pub fn form(&self) -> TargetForm {
+        self.form
+    }
+
+    /// Lowercase, only an absolute-form target has one.
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.as_deref()
+    }
+
+    /// Lowercase, IPv6 addresses in brackets. Origin-form and asterisk-form
+    /// targets have none, the `Host` header names it.
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Still percent-encoded, empty for the authority-form.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Still percent-encoded, without the `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// The decoded segments following each `/` of the path, `/` itself has
+    /// one empty segment.
+    pub fn path_segments(&self) -> Result<Vec<String>, RhttpError> {
+        match self.path.strip_prefix('/') {
+            Some(path) => path.split('/').map(percent_decode).collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The decoded `key=value` pairs of the query, a `+` is a space as in
+    /// `application/x-www-form-urlencoded`.
+    pub fn query_pairs(&self) -> Result<Vec<(String, String)>, RhttpError> {
+        let decode = |value: &str| percent_decode(&value.replace('+', " "));
+        self.query
+            .iter()
+            .flat_map(|query| query.split('&'))
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                Ok((decode(key)?, decode(value)?))
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Url {
+    type Err = RhttpError;
+
+    /// An origin-form or absolute-form target.
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        Self::parse_request_target(Method::Get, target)
+    }
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{scheme}://")?;
+        }
+        if let Some(host) = &self.host {
+            f.write_str(host)?;
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+        f.write_str(&self.path)?;
+        match &self.query {
+            Some(query) => write!(f, "?{query}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The path of a request target as the client sent it, before the
+/// normalization [`Url`] applies. Empty for the authority-form.
+pub fn raw_path(target: &str) -> &str {
+    let path = match target.split_once("://") {
+        _ if target.starts_with('/') || target == "*" => target,
+        Some((_, rest)) => &rest[rest.find(['/', '?']).unwrap_or(rest.len())..],
+        None => "",
+    };
+    path.split_once('?').map_or(path, |(path, _)| path)
+}
+
+/// Decodes every `%XX` escape, the result has to be UTF-8.
+pub fn percent_decode(value: &str) -> Result<String, RhttpError> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+    while at < bytes.len() {
+        match bytes[at] {
+            b'%' => {
+                decoded.push(escaped(bytes, at).ok_or_else(|| invalid(value, "escape"))?);
+                at += 3;
+            }
+            b => {
+                decoded.push(b);
+                at += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid(value, "non UTF-8 escape"))
+}
+
+/// The byte of the escape starting at `at`.
+fn escaped(bytes: &[u8], at: usize) -> Option<u8> {
+    let hex = std::str::from_utf8(bytes.get(at + 1..at + 3)?).ok()?;
+    match hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        true => u8::from_str_radix(hex, 16).ok(),
+        false => None,
+    }
+}
+
+fn invalid(target: &str, reason: &str) -> RhttpError {
+    InvalidUrl(format!("{reason} in {target:?}"))
+}
+
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~".contains(&b)
+}
+
+fn is_sub_delim(b: u8) -> bool {
+    b"!$&'()*+,;=".contains(&b)
+}
+
+/// `pchar` and `/`, `query` also allows `?`.
+fn is_path_char(b: u8) -> bool {
+    is_unreserved(b) || is_sub_delim(b) || b":@/".contains(&b)
+}
+
+fn is_query_char(b: u8) -> bool {
+    is_path_char(b) || b == b'?'
+}
+
+/// `scheme`, RFC 3986 section 3.1.
+fn is_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b))
+}
+
+/// Checks the characters of `value` and normalizes its escapes.
+fn normalize(value: &str, allowed: fn(u8) -> bool) -> Result<String, RhttpError> {
+    let bytes = value.as_bytes();
+    let mut normalized = String::with_capacity(value.len());
+    let mut at = 0;
+    while at < bytes.len() {
+        match bytes[at] {
+            b'%' => {
+                let byte = escaped(bytes, at).ok_or_else(|| invalid(value, "escape"))?;
+                match is_unreserved(byte) {
+                    true => normalized.push(char::from(byte)),
+                    false => normalized.push_str(&format!("%{byte:02X}")),
+                }
+                at += 3;
+            }
+            b if allowed(b) => {
+                normalized.push(char::from(b));
+                at += 1;
+            }
+            _ => return Err(invalid(value, "invalid character")),
+        }
+    }
+    Ok(normalized)
+}
+
+/// The normalized path and query of `/path?query`.
+fn split_query(target: &str) -> Result<(String, Option<String>), RhttpError> {
+    match target.split_once('?') {
+        Some((path, query)) => Ok((
+            normalize(path, is_path_char)?,
+            Some(normalize(query, is_query_char)?),
+        )),
+        None => Ok((normalize(target, is_path_char)?, None)),
+    }
+}
+
+/// `host[:port]`, user information is deprecated for http, RFC 9110
+/// section 4.2.4, and rejected.
+fn parse_authority(authority: &str) -> Result<(String, Option<u16>), RhttpError> {
+    if authority.contains('@') {
+        return Err(invalid(authority, "user information"));
+    }
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(literal) => {
+            let (address, rest) = literal
+                .split_once(']')
+                .ok_or_else(|| invalid(authority, "IP literal"))?;
+            address
+                .parse::<Ipv6Addr>()
+                .map_err(|_| invalid(authority, "IPv6 address"))?;
+            let port = match rest {
+                "" => None,
+                rest => Some(
+                    rest.strip_prefix(':')
+                        .ok_or_else(|| invalid(authority, "port"))?,
+                ),
+            };
+            (&authority[..address.len() + 2], port)
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let reg_name = |b: u8| is_unreserved(b) || is_sub_delim(b);
+    if host.is_empty() || (!host.starts_with('[') && !host.bytes().all(reg_name)) {
+        return Err(invalid(authority, "host"));
+    }
+    let port = match port {
+        None | Some("") => None,
+        Some(port) if port.bytes().all(|b| b.is_ascii_digit()) => {
+            Some(port.parse().map_err(|_| invalid(authority, "port"))?)
+        }
+        Some(_) => return Err(invalid(authority, "port")),
+    };
+    Ok((host.to_ascii_lowercase(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_form() {
+        let url = "/caf%c3%a9/a%2Fb/%7Euser?q=a+b&x=%26&flag"
+            .parse::<Url>()
+            .unwrap();
+        assert_eq!(url.form(), TargetForm::Origin);
+        assert_eq!(url.path(), "/caf%C3%A9/a%2Fb/~user");
+        assert_eq!(url.query(), Some("q=a+b&x=%26&flag"));
+        assert_eq!(url.host(), None);
+        assert_eq!(url.path_segments().unwrap(), ["café", "a/b", "~user"]);
+        assert_eq!(
+            url.query_pairs().unwrap(),
+            [
+                (String::from("q"), String::from("a b")),
+                (String::from("x"), String::from("&")),
+                (String::from("flag"), String::new()),
+            ]
+        );
+        assert_eq!(url.to_string(), "/caf%C3%A9/a%2Fb/~user?q=a+b&x=%26&flag");
+        assert_eq!(
+            raw_path("/caf%c3%a9/a%2Fb/%7Euser?q=a+b&x=%26&flag"),
+            "/caf%c3%a9/a%2Fb/%7Euser"
+        );
+        assert_eq!("/".parse::<Url>().unwrap().path_segments().unwrap(), [""]);
+
+        for target in ["/a#frag", "/a b", "/%zz", "/%4", "/é", "/%FF"] {
+            let url = target.parse::<Url>();
+            // a valid escape that is not UTF-8 only fails when decoded
+            let decoded = url.and_then(|url| url.path_segments());
+            assert!(decoded.is_err(), "{target}");
+        }
+    }
+
+    #[test]
+    fn test_absolute_form() {
+        let url = "HTTP://Example.TEST:8080?x=1".parse::<Url>().unwrap();
+        assert_eq!(url.form(), TargetForm::Absolute);
+        assert_eq!(url.scheme(), Some("http"));
+        assert_eq!(url.host(), Some("example.test"));
+        assert_eq!(url.port(), Some(8080));
+        assert_eq!(url.path(), "/");
+        assert_eq!(url.to_string(), "http://example.test:8080/?x=1");
+        assert_eq!(raw_path("HTTP://Example.TEST:8080?x=1"), "");
+        assert_eq!(raw_path("http://example.test/%7ea?x=1"), "/%7ea");
+
+        let url = "http://[::1]/a".parse::<Url>().unwrap();
+        assert_eq!((url.host(), url.port()), (Some("[::1]"), None));
+
+        for target in [
+            "http://user@example.test/",
+            "http:///a",
+            "http://example.test:port/",
+            "http://[::g]/",
+            "1http://example.test/",
+            "example.test/a",
+        ] {
+            assert!(target.parse::<Url>().is_err(), "{target}");
+        }
+    }
+
+    #[test]
+    fn test_authority_and_asterisk_form() {
+        let url = Url::parse_request_target(Method::Connect, "example.test:443").unwrap();
+        assert_eq!(url.form(), TargetForm::Authority);
+        assert_eq!((url.host(), url.port()), (Some("example.test"), Some(443)));
+        assert_eq!(url.to_string(), "example.test:443");
+        assert_eq!(raw_path("example.test:443"), "");
+        assert!(Url::parse_request_target(Method::Connect, "example.test").is_err());
 
+        let url = Url::parse_request_target(Method::Options, "*").unwrap();
+        assert_eq!(url.form(), TargetForm::Asterisk);
+        assert_eq!(url.to_string(), "*");
+        assert!(Url::parse_request_target(Method::Get, "*").is_err());
+    }
 }